@@ -0,0 +1,90 @@
+use crate::joypaths::InputDevice;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// A multi-port adapter, picked out by USB ids and/or the port it is plugged into.
+// Each controller port on the adapter is a USB interface number.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Adapter {
+    // Hex, like "0079"
+    #[serde(default, with = "crate::hex_id")]
+    pub vendor_id: Option<u16>,
+    #[serde(default, with = "crate::hex_id")]
+    pub product_id: Option<u16>,
+    // USB port path without the interface, like "1.2:1"
+    #[serde(default)]
+    pub path: Option<String>,
+    // Ports in use, all of them when unset
+    #[serde(default)]
+    pub ports: Option<Vec<u32>>,
+    // Fixed player names per port, instead of the identity strategy
+    #[serde(default)]
+    pub names: HashMap<u32, String>,
+}
+
+fn id_matches(wanted: Option<u16>, actual: Option<u16>) -> bool {
+    match wanted {
+        Some(wanted) => actual == Some(wanted),
+        None => true,
+    }
+}
+
+impl Adapter {
+    pub fn matches(&self, device: &InputDevice) -> bool {
+        let path = match &self.path {
+            None => true,
+            Some(path) => device.usb_port() == Some(path.as_str()),
+        };
+        path && id_matches(self.vendor_id, device.vendor_id())
+            && id_matches(self.product_id, device.product_id())
+    }
+
+    pub fn enabled(&self, port: u32) -> bool {
+        match &self.ports {
+            Some(ports) => ports.contains(&port),
+            None => true,
+        }
+    }
+
+    pub fn fixed_name(&self, port: u32) -> Option<&str> {
+        self.names.get(&port).map(|n| n.as_str())
+    }
+}
+
+pub fn find<'a>(adapters: &'a [Adapter], device: &InputDevice) -> Option<&'a Adapter> {
+    adapters.iter().find(|a| a.matches(device))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id_path: &str, vendor: &str) -> InputDevice {
+        InputDevice::fake(
+            "/dev/input/event4",
+            &[("ID_PATH", id_path), ("ID_VENDOR_ID", vendor)],
+        )
+    }
+
+    #[test]
+    fn adapters() {
+        let json = r#"[
+            {"vendor_id": "0079", "ports": [0, 1], "names": {"1": "lefty"}},
+            {"path": "2:1"}
+        ]"#;
+        let adapters: Vec<Adapter> = serde_json::from_str(json).unwrap();
+
+        let mayflash = device("pci-0000:00:14.0-usb-0:1:1.1", "0079");
+        let adapter = find(&adapters, &mayflash).unwrap();
+        assert!(adapter.enabled(1));
+        assert!(!adapter.enabled(2));
+        assert_eq!(adapter.fixed_name(1), Some("lefty"));
+
+        let other = device("pci-0000:00:14.0-usb-0:2:1.3", "1234");
+        assert!(find(&adapters, &other).unwrap().enabled(3));
+        assert!(find(&adapters, &device("pci-0000:00:14.0-usb-0:3:1.0", "1234")).is_none());
+
+        // A typo fails the load rather than switching the adapter off
+        assert!(serde_json::from_str::<Vec<Adapter>>(r#"[{"vendor_id": "oo79"}]"#).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum Aggregation {
+    #[default]
+    MeanThreshold,
+    Majority,
+    Unanimous,
+    Any,
+    Median,
+    TrimmedMean {
+        trim: f32,
+    },
+    Weighted {
+        weights: HashMap<String, f32>,
+    },
+    // Buttons fire when enough players pressed within window_ms, and stay down for hold_ms
+    Window {
+        window_ms: u64,
+        hold_ms: u64,
+    },
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TeamAggregation {
+    #[serde(default)]
+    pub default: Aggregation,
+    #[serde(default)]
+    pub buttons: HashMap<String, Aggregation>,
+    #[serde(default)]
+    pub axes: HashMap<String, Aggregation>,
+}
+
+impl TeamAggregation {
+    pub fn for_button(&self, b: &str) -> &Aggregation {
+        self.buttons.get(b).unwrap_or(&self.default)
+    }
+
+    pub fn for_axis(&self, a: &str) -> &Aggregation {
+        self.axes.get(a).unwrap_or(&self.default)
+    }
+}
+
+pub struct Sample<'a> {
+    pub player: &'a str,
+    pub value: f32,
+}
+
+fn mean(values: &[f32]) -> f32 {
+    match values.len() {
+        0 => 0f32,
+        n => values.iter().sum::<f32>() / n as f32,
+    }
+}
+
+fn sorted_values(samples: &[Sample]) -> Vec<f32> {
+    let mut values: Vec<f32> = samples.iter().map(|s| s.value).collect();
+    values.sort_by(|a, b| a.total_cmp(b));
+    values
+}
+
+fn median(samples: &[Sample]) -> f32 {
+    let values = sorted_values(samples);
+    let n = values.len();
+    match n {
+        0 => 0f32,
+        n if n % 2 == 1 => values[n / 2],
+        n => (values[n / 2 - 1] + values[n / 2]) / 2f32,
+    }
+}
+
+fn trimmed_mean(samples: &[Sample], trim: f32) -> f32 {
+    let values = sorted_values(samples);
+    let cut = (values.len() as f32 * trim.clamp(0f32, 0.5f32)).floor() as usize;
+    if cut * 2 >= values.len() {
+        return median(samples);
+    }
+    mean(&values[cut..values.len() - cut])
+}
+
+fn weighted_mean(samples: &[Sample], weights: &HashMap<String, f32>) -> f32 {
+    let mut sum = 0f32;
+    let mut total = 0f32;
+    for s in samples.iter() {
+        let w = *weights.get(s.player).unwrap_or(&1f32);
+        sum += w * s.value;
+        total += w;
+    }
+    if total <= 0f32 {
+        return 0f32;
+    }
+    sum / total
+}
+
+fn is_pressed(value: f32) -> bool {
+    value > 0.5
+}
+
+impl Aggregation {
+    // Majority, unanimous and any count pressed players and ignore the threshold
+    pub fn uses_threshold(&self) -> bool {
+        !matches!(
+            self,
+            Aggregation::Majority | Aggregation::Unanimous | Aggregation::Any
+        )
+    }
+
+    pub fn button(&self, samples: &[Sample], threshold: f32) -> bool {
+        use Aggregation::*;
+
+        if samples.is_empty() {
+            return false;
+        }
+
+        let values: Vec<f32> = samples.iter().map(|s| s.value).collect();
+        let pressed = values.iter().filter(|v| is_pressed(**v)).count();
+        match self {
+            MeanThreshold | Window { .. } => mean(&values) > threshold,
+            Majority => pressed * 2 > values.len(),
+            Unanimous => pressed == values.len(),
+            Any => pressed > 0,
+            Median => median(samples) > threshold,
+            TrimmedMean { trim } => trimmed_mean(samples, *trim) > threshold,
+            Weighted { weights } => weighted_mean(samples, weights) > threshold,
+        }
+    }
+
+    pub fn axis(&self, samples: &[Sample]) -> f32 {
+        use Aggregation::*;
+
+        let values: Vec<f32> = samples.iter().map(|s| s.value).collect();
+        let positive: Vec<f32> = values.iter().copied().filter(|v| *v > 0f32).collect();
+        let negative: Vec<f32> = values.iter().copied().filter(|v| *v < 0f32).collect();
+        match self {
+            MeanThreshold | Window { .. } => mean(&values),
+            Majority => match values.len() {
+                n if positive.len() * 2 > n => mean(&positive),
+                n if negative.len() * 2 > n => mean(&negative),
+                _ => 0f32,
+            },
+            Unanimous => match values.len() {
+                0 => 0f32,
+                n if positive.len() == n || negative.len() == n => mean(&values),
+                _ => 0f32,
+            },
+            Any => values
+                .iter()
+                .copied()
+                .fold(0f32, |a, v| if v.abs() > a.abs() { v } else { a }),
+            Median => median(samples),
+            TrimmedMean { trim } => trimmed_mean(samples, *trim),
+            Weighted { weights } => weighted_mean(samples, weights),
+        }
+    }
+}
+
+// One sample per name, for tests
+#[cfg(test)]
+pub fn samples<'a>(names: &'a [&'a str], values: &[f32]) -> Vec<Sample<'a>> {
+    names
+        .iter()
+        .zip(values.iter())
+        .map(|(n, v)| Sample {
+            player: n,
+            value: *v,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buttons() {
+        let names = ["a", "b", "c", "d"];
+        let three_of_four = samples(&names, &[1f32, 1f32, 1f32, 0f32]);
+        let two_of_four = samples(&names, &[1f32, 1f32, 0f32, 0f32]);
+
+        assert!(Aggregation::MeanThreshold.button(&three_of_four, 0.7));
+        assert!(!Aggregation::MeanThreshold.button(&three_of_four, 0.8));
+        assert!(Aggregation::Majority.button(&three_of_four, 0.9));
+        assert!(!Aggregation::Majority.button(&two_of_four, 0.1));
+        assert!(!Aggregation::Unanimous.button(&three_of_four, 0.1));
+        assert!(Aggregation::Any.button(&two_of_four, 0.9));
+        assert!(!Aggregation::Any.button(&[], 0.0));
+        assert!(!Aggregation::Majority.uses_threshold());
+        assert!(Aggregation::Median.uses_threshold());
+
+        let mut weights = HashMap::new();
+        weights.insert("a".to_string(), 5f32);
+        let weighted = Aggregation::Weighted { weights };
+        assert!(weighted.button(&samples(&names, &[1f32, 0f32, 0f32, 0f32]), 0.5));
+    }
+
+    #[test]
+    fn axes() {
+        let names = ["a", "b", "c", "d", "e"];
+        let mixed = samples(&names, &[1f32, 1f32, 1f32, -1f32, 0f32]);
+
+        assert_eq!(Aggregation::MeanThreshold.axis(&mixed), 0.4);
+        assert_eq!(Aggregation::Majority.axis(&mixed), 1f32);
+        assert_eq!(Aggregation::Unanimous.axis(&mixed), 0f32);
+        assert_eq!(Aggregation::Median.axis(&mixed), 1f32);
+        assert_eq!(
+            Aggregation::TrimmedMean { trim: 0.2 }.axis(&mixed),
+            2f32 / 3f32
+        );
+        assert_eq!(
+            Aggregation::Any.axis(&samples(&names, &[0.2, -0.9, 0.5, 0f32, 0f32])),
+            -0.9
+        );
+
+        // A NaN sample sorts past the others rather than panicking
+        let nan = samples(&names, &[f32::NAN, 0.5f32, 0.5f32]);
+        assert_eq!(Aggregation::Median.axis(&nan), 0.5);
+    }
+
+    #[test]
+    fn config() {
+        let json = r#"{
+            "default": {"strategy": "majority"},
+            "buttons": {"Start": {"strategy": "unanimous"}},
+            "axes": {"Xright": {"strategy": "trimmed_mean", "trim": 0.25}}
+        }"#;
+        let team: TeamAggregation = serde_json::from_str(json).unwrap();
+        assert!(matches!(team.for_button("A"), Aggregation::Majority));
+        assert!(matches!(team.for_button("Start"), Aggregation::Unanimous));
+        assert!(matches!(
+            team.for_axis("Xright"),
+            Aggregation::TrimmedMean { .. }
+        ));
+    }
+}
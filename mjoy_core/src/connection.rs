@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingPolicy {
+    // Carry on with whoever is left
+    #[default]
+    Drop,
+    // Hold the team's last output until the player is back or the grace period ends
+    Freeze,
+    // Release everything until the player is back or the grace period ends
+    Neutral,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionConfig {
+    #[serde(default = "default_grace_ms")]
+    pub grace_ms: u64,
+    #[serde(default)]
+    pub on_missing: MissingPolicy,
+    // A controller nobody owns takes over the slot of a missing player
+    #[serde(default)]
+    pub adopt_new_devices: bool,
+}
+
+fn default_grace_ms() -> u64 {
+    3000
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        ConnectionConfig {
+            grace_ms: default_grace_ms(),
+            on_missing: MissingPolicy::default(),
+            adopt_new_devices: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Connected,
+    // Still inside the grace period
+    Missing(Instant),
+    Gone,
+}
+
+pub struct Connections {
+    config: ConnectionConfig,
+    // Every player slot from the teamlock, in team order
+    players: Vec<String>,
+    status: HashMap<String, Status>,
+    // Controller name -> the player slot it stands in for
+    aliases: HashMap<String, String>,
+}
+
+impl Connections {
+    pub fn new(config: &ConnectionConfig, players: Vec<String>) -> Self {
+        let status = players
+            .iter()
+            .map(|p| (p.clone(), Status::Connected))
+            .collect();
+        Connections {
+            config: config.clone(),
+            players,
+            status,
+            aliases: HashMap::new(),
+        }
+    }
+
+    pub fn policy(&self) -> MissingPolicy {
+        self.config.on_missing
+    }
+
+    pub fn status(&self, player: &str) -> Status {
+        *self.status.get(player).unwrap_or(&Status::Gone)
+    }
+
+    pub fn waiting_for_any(&self, players: &[String]) -> bool {
+        players
+            .iter()
+            .any(|p| matches!(self.status(p), Status::Missing(_)))
+    }
+
+    // Which absent slot a stranger takes first: one that dropped since the last
+    // update, then missing players, latest to drop first, then gone or never seen
+    fn adoption_order(&self, player: &str) -> (u8, Option<Reverse<Instant>>) {
+        match self.status(player) {
+            Status::Connected => (0, None),
+            Status::Missing(since) => (1, Some(Reverse(since))),
+            Status::Gone => (2, None),
+        }
+    }
+
+    // Call with freshly discovered controller names, before `update`. Returns which
+    // controller stands in for which player slot.
+    pub fn reattach(&mut self, present: &[String]) -> HashMap<String, String> {
+        // The original controller is back, so it gets its slot back
+        self.aliases
+            .retain(|name, slot| present.contains(name) && !present.contains(slot));
+
+        if self.config.adopt_new_devices {
+            let mut strangers: Vec<&String> = present
+                .iter()
+                .filter(|n| !self.players.contains(n) && !self.aliases.contains_key(*n))
+                .collect();
+            strangers.sort();
+            for stranger in strangers {
+                let taken: Vec<&String> = self.aliases.values().collect();
+                let slot = self
+                    .players
+                    .iter()
+                    .filter(|p| !present.contains(p) && !taken.contains(p))
+                    .min_by_key(|p| self.adoption_order(p));
+                let slot = match slot {
+                    Some(slot) => slot.clone(),
+                    None => break,
+                };
+                println!("{} takes over the slot of {}", stranger, slot);
+                self.aliases.insert(stranger.clone(), slot);
+            }
+        }
+
+        self.aliases.clone()
+    }
+
+    pub fn update(&mut self, present: &[String], now: Instant) {
+        let grace = Duration::from_millis(self.config.grace_ms);
+
+        for player in self.players.iter() {
+            let status = self.status.get_mut(player).unwrap();
+            let next = match (*status, present.contains(player)) {
+                (Status::Connected, true) => Status::Connected,
+                (_, true) => {
+                    println!("{} reconnected", player);
+                    Status::Connected
+                }
+                (Status::Connected, false) => {
+                    println!("{} disconnected", player);
+                    Status::Missing(now)
+                }
+                (Status::Missing(since), false) if now.duration_since(since) >= grace => {
+                    println!("{} is gone, carrying on without them", player);
+                    Status::Gone
+                }
+                (other, false) => other,
+            };
+            *status = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn grace_and_reattach() {
+        let config = ConnectionConfig {
+            grace_ms: 100,
+            adopt_new_devices: true,
+            ..Default::default()
+        };
+        let players = vec!["ann".to_string(), "bob".to_string()];
+        let mut connections = Connections::new(&config, players.clone());
+        let start = Instant::now();
+
+        connections.update(&names(&["ann", "bob"]), start);
+        assert!(!connections.waiting_for_any(&players));
+
+        connections.update(&names(&["ann"]), start);
+        assert_eq!(connections.status("bob"), Status::Missing(start));
+        assert!(connections.waiting_for_any(&players));
+        connections.update(&names(&["ann"]), start + Duration::from_millis(100));
+        assert_eq!(connections.status("bob"), Status::Gone);
+
+        // Bob's pad comes back on another port with a new name
+        let aliases = connections.reattach(&names(&["ann", "zed"]));
+        assert_eq!(aliases["zed"], "bob");
+        connections.update(&names(&["ann", "bob"]), start + Duration::from_millis(200));
+        assert_eq!(connections.status("bob"), Status::Connected);
+    }
+
+    #[test]
+    fn strangers_take_missing_slots_before_gone_ones() {
+        let config = ConnectionConfig {
+            grace_ms: 100,
+            adopt_new_devices: true,
+            ..Default::default()
+        };
+        let players = names(&["ann", "bob", "cat"]);
+        let mut connections = Connections::new(&config, players);
+        let start = Instant::now();
+
+        // Ann is long gone, bob only just dropped
+        connections.update(&names(&["bob", "cat"]), start);
+        connections.update(&names(&["bob", "cat"]), start + Duration::from_millis(100));
+        connections.update(&names(&["cat"]), start + Duration::from_millis(150));
+        assert_eq!(connections.status("ann"), Status::Gone);
+        assert!(matches!(connections.status("bob"), Status::Missing(_)));
+
+        let aliases = connections.reattach(&names(&["cat", "zed"]));
+        assert_eq!(aliases["zed"], "bob");
+        let aliases = connections.reattach(&names(&["cat", "zed", "yan"]));
+        assert_eq!(aliases["yan"], "ann");
+    }
+
+    #[test]
+    fn adopting_is_opt_in() {
+        let config = ConnectionConfig::default();
+        let mut connections = Connections::new(&config, names(&["ann"]));
+        connections.update(&[], Instant::now());
+        assert!(connections.reattach(&names(&["zed"])).is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::aggregate::Sample;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+struct PlayerPress {
+    held: bool,
+    last_press: Option<Instant>,
+}
+
+// Tracks recent presses per player for one output button, for the `window` aggregation
+#[derive(Debug, Default)]
+pub struct ConsensusWindow {
+    players: HashMap<String, PlayerPress>,
+    fired_until: Option<Instant>,
+}
+
+impl ConsensusWindow {
+    pub fn update(
+        &mut self,
+        samples: &[Sample],
+        threshold: f32,
+        window: Duration,
+        hold: Duration,
+        now: Instant,
+    ) -> bool {
+        for s in samples.iter() {
+            let press = self.players.entry(s.player.to_string()).or_default();
+            let pressed = s.value > 0.5;
+            if pressed && !press.held {
+                press.last_press = Some(now);
+            }
+            press.held = pressed;
+        }
+
+        let recent: Vec<&str> = samples
+            .iter()
+            .filter(|s| match self.players[s.player].last_press {
+                Some(t) => now.duration_since(t) <= window,
+                None => false,
+            })
+            .map(|s| s.player)
+            .collect();
+
+        if !samples.is_empty() && recent.len() as f32 / samples.len() as f32 > threshold {
+            self.fired_until = Some(now + hold);
+            // Each press only counts towards one team press
+            for player in recent {
+                if let Some(press) = self.players.get_mut(player) {
+                    press.last_press = None;
+                }
+            }
+        }
+
+        match self.fired_until {
+            Some(t) => now < t,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregate::samples;
+
+    #[test]
+    fn staggered_taps_fire_once() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let window = Duration::from_millis(150);
+        let hold = Duration::from_millis(50);
+        let mut consensus = ConsensusWindow::default();
+        let mut update = |values: &[f32], ms| {
+            consensus.update(
+                &samples(&["a", "b", "c"], values),
+                0.5,
+                window,
+                hold,
+                at(ms),
+            )
+        };
+
+        assert!(!update(&[1.0, 0.0, 0.0], 0));
+        assert!(!update(&[0.0, 0.0, 0.0], 40));
+        assert!(update(&[0.0, 1.0, 0.0], 100));
+        assert!(update(&[0.0, 1.0, 0.0], 140));
+        assert!(!update(&[0.0, 1.0, 0.0], 160));
+
+        // Too far apart to count together
+        assert!(!update(&[0.0, 0.0, 1.0], 400));
+        assert!(!update(&[1.0, 0.0, 1.0], 600));
+    }
+}
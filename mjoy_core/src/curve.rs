@@ -0,0 +1,192 @@
+use crate::injoy::AxisSource;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Shapes 0..1 deflection into 0..1 output; negative values are mirrored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum Curve {
+    Linear,
+    Power {
+        exponent: f32,
+    },
+    // Blends towards smoothstep: fine control near the centre and the edges.
+    // A strength of 0 is linear, 1 is the full S.
+    #[serde(rename = "s_curve")]
+    Smooth {
+        strength: f32,
+    },
+    // (input, output) pairs, linearly interpolated and held past either end
+    Table {
+        points: Vec<[f32; 2]>,
+    },
+}
+
+impl Default for Curve {
+    fn default() -> Self {
+        Curve::Power { exponent: 2.0 }
+    }
+}
+
+fn table(points: &[[f32; 2]], x: f32) -> f32 {
+    let first = match points.first() {
+        Some(first) => first,
+        None => return x,
+    };
+    if x <= first[0] {
+        return first[1];
+    }
+    for pair in points.windows(2) {
+        let ([x0, y0], [x1, y1]) = (pair[0], pair[1]);
+        if x <= x1 {
+            if x1 <= x0 {
+                return y1;
+            }
+            return y0 + (y1 - y0) * (x - x0) / (x1 - x0);
+        }
+    }
+    points[points.len() - 1][1]
+}
+
+impl Curve {
+    pub fn shape(&self, x: f32) -> f32 {
+        match self {
+            Curve::Linear => x,
+            Curve::Power { exponent } => x.powf(*exponent),
+            Curve::Smooth { strength } => {
+                let s = x * x * (3f32 - 2f32 * x);
+                x + (s - x) * strength.clamp(0f32, 1f32)
+            }
+            Curve::Table { points } => table(points, x),
+        }
+    }
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    #[serde(default)]
+    pub curve: Curve,
+    // Team deflection below this is centred
+    #[serde(default)]
+    pub inner_deadzone: f32,
+    // Team deflection within this of the edge is full
+    #[serde(default)]
+    pub outer_deadzone: f32,
+    // Output at full deflection, 1 being the whole axis
+    #[serde(default = "one")]
+    pub range: f32,
+    // Distinct output values per direction, unlimited when unset
+    #[serde(default)]
+    pub steps: Option<u32>,
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Response {
+            curve: Curve::default(),
+            inner_deadzone: 0f32,
+            outer_deadzone: 0f32,
+            range: one(),
+            steps: None,
+        }
+    }
+}
+
+impl Response {
+    pub fn apply(&self, value: f32) -> f32 {
+        let x = value.abs().min(1f32);
+        if x <= self.inner_deadzone {
+            return 0f32;
+        }
+        let live = (1f32 - self.inner_deadzone - self.outer_deadzone).max(f32::EPSILON);
+        let x = ((x - self.inner_deadzone) / live).min(1f32);
+
+        let mut y = self.curve.shape(x).clamp(0f32, 1f32) * self.range;
+        if let Some(steps) = self.steps {
+            let steps = steps.max(1) as f32;
+            y = (y * steps).round() / steps;
+        }
+        value.signum() * y
+    }
+}
+
+fn linear() -> Response {
+    Response {
+        curve: Curve::Linear,
+        ..Response::default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AxisResponses {
+    // For averaged D-pads, which only move in steps
+    #[serde(default)]
+    pub default: Response,
+    // For axes anyone on the team steers with a stick, which are already smooth
+    #[serde(default = "linear")]
+    pub analog: Response,
+    // Keyed by layout axis name, whatever the source
+    #[serde(default)]
+    pub axes: HashMap<String, Response>,
+}
+
+impl Default for AxisResponses {
+    fn default() -> Self {
+        AxisResponses {
+            default: Response::default(),
+            analog: linear(),
+            axes: HashMap::new(),
+        }
+    }
+}
+
+impl AxisResponses {
+    pub fn for_axis(&self, a: &str, source: AxisSource) -> &Response {
+        match (self.axes.get(a), source) {
+            (Some(response), _) => response,
+            (None, AxisSource::Dpad) => &self.default,
+            (None, AxisSource::Analog) => &self.analog,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses() {
+        let json = r#"{
+            "axes": {
+                "Xright": {"curve": {"shape": "linear"}, "inner_deadzone": 0.1, "outer_deadzone": 0.1},
+                "Yup": {"curve": {"shape": "table", "points": [[0, 0], [0.5, 0.2], [1, 1]]}, "steps": 4},
+                "CRight": {"curve": {"shape": "s_curve", "strength": 1}, "range": 0.5}
+            }
+        }"#;
+        let responses: AxisResponses = serde_json::from_str(json).unwrap();
+
+        // The old fixed squared curve stays the D-pad default, sticks are linear
+        let default = responses.for_axis("CUp", AxisSource::Dpad);
+        assert_eq!(default.apply(-0.5), -0.25);
+        assert_eq!(default.apply(1.5), 1.0);
+        let analog = responses.for_axis("CUp", AxisSource::Analog);
+        assert_eq!(analog.apply(-0.5), -0.5);
+
+        let x = responses.for_axis("Xright", AxisSource::Analog);
+        assert_eq!(x.apply(0.05), 0.0);
+        assert!((x.apply(0.5) - 0.5).abs() < 1e-6);
+        assert_eq!(x.apply(-0.95), -1.0);
+
+        let y = responses.for_axis("Yup", AxisSource::Analog);
+        assert_eq!(y.apply(0.5), 0.25);
+        assert_eq!(y.apply(0.75), 0.5);
+
+        let c = responses.for_axis("CRight", AxisSource::Analog);
+        assert_eq!(c.apply(0.5), 0.25);
+        assert!(c.apply(0.1) < 0.05 * 0.5);
+    }
+}
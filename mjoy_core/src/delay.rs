@@ -0,0 +1,96 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DelayConfig {
+    #[serde(default)]
+    pub delay_ms: u64,
+    // Extra random delay of up to this many ms on top of delay_ms
+    #[serde(default)]
+    pub jitter_ms: u64,
+}
+
+// Holds a team's output frames until they are due. Release times never go backwards,
+// so jitter stretches the delay without reordering inputs.
+pub struct DelayLine<T> {
+    config: DelayConfig,
+    rng: StdRng,
+    queue: VecDeque<(Instant, T)>,
+    last_release: Option<Instant>,
+}
+
+impl<T> DelayLine<T> {
+    pub fn new(config: DelayConfig, seed: u64) -> Self {
+        DelayLine {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+            queue: VecDeque::new(),
+            last_release: None,
+        }
+    }
+
+    pub fn push(&mut self, frame: T, now: Instant) {
+        let mut millis = self.config.delay_ms;
+        if self.config.jitter_ms > 0 {
+            millis += self.rng.gen_range(0..=self.config.jitter_ms);
+        }
+        let mut release = now + Duration::from_millis(millis);
+        if let Some(last) = self.last_release {
+            release = release.max(last);
+        }
+        self.last_release = Some(release);
+        self.queue.push_back((release, frame));
+    }
+
+    // Latest frame that is due, skipping any older due frames
+    pub fn pop(&mut self, now: Instant) -> Option<T> {
+        let mut due = None;
+        while let Some((release, _)) = self.queue.front() {
+            if *release > now {
+                break;
+            }
+            due = self.queue.pop_front().map(|(_, frame)| frame);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_delay_passes_through() {
+        let now = Instant::now();
+        let mut line = DelayLine::new(DelayConfig::default(), 0);
+        line.push(1, now);
+        assert_eq!(line.pop(now), Some(1));
+        assert_eq!(line.pop(now), None);
+    }
+
+    #[test]
+    fn frames_arrive_late_and_in_order() {
+        let start = Instant::now();
+        let config = DelayConfig {
+            delay_ms: 100,
+            jitter_ms: 50,
+        };
+        let mut line = DelayLine::new(config, 3);
+        for i in 0..10u64 {
+            line.push(i, start + Duration::from_millis(i * 10));
+        }
+        assert_eq!(line.pop(start + Duration::from_millis(99)), None);
+
+        let mut seen = Vec::new();
+        for ms in 100..300 {
+            if let Some(i) = line.pop(start + Duration::from_millis(ms)) {
+                seen.push(i);
+            }
+        }
+        assert_eq!(seen.last(), Some(&9));
+        assert!(seen.windows(2).all(|w| w[0] < w[1]));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::layout::{AxisControl, ButtonControl, Layout, OutAxis};
+use crate::outjoy::Frame;
+use crate::output::Output;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+// Buttons Dolphin's pipe input understands
+const BUTTONS: [&str; 12] = [
+    "A", "B", "X", "Y", "Z", "START", "L", "R", "D_UP", "D_DOWN", "D_LEFT", "D_RIGHT",
+];
+
+// From the layout, else the control's own name when Dolphin knows it
+fn button_name(control: &ButtonControl) -> Option<String> {
+    match &control.dolphin {
+        Some(name) => Some(name.clone()),
+        None => {
+            let name = control.name.to_uppercase();
+            BUTTONS.contains(&name.as_str()).then_some(name)
+        }
+    }
+}
+
+// "MAIN x" is the x half of the main stick, a bare "L" is an analog trigger
+fn axis_target(control: &AxisControl) -> (String, Option<usize>) {
+    let target = match &control.dolphin {
+        Some(target) => target.clone(),
+        None => match control.output {
+            OutAxis::X => "MAIN x".to_string(),
+            OutAxis::Y => "MAIN y".to_string(),
+            OutAxis::RX => "C x".to_string(),
+            OutAxis::RY => "C y".to_string(),
+        },
+    };
+    match target.split_once(' ') {
+        Some((stick, "y")) => (stick.to_string(), Some(1)),
+        Some((stick, _)) => (stick.to_string(), Some(0)),
+        None => (target, None),
+    }
+}
+
+// One command per button, stick and trigger, keyed by what it sets. Sticks and
+// triggers go from 0 to 1 with 0.5 centred; y keeps the uinput direction and is
+// left to the Dolphin profile to map.
+pub fn commands(layout: &Layout, frame: &Frame) -> Vec<(String, String)> {
+    let mut commands = Vec::new();
+
+    let mut sticks: Vec<(String, [f32; 2])> = Vec::new();
+    for (control, value) in layout.axes.iter().zip(frame.axes.iter()) {
+        let scaled = (value.clamp(-1f32, 1f32) + 1f32) / 2f32;
+        match axis_target(control) {
+            (stick, Some(half)) => match sticks.iter_mut().find(|(s, _)| *s == stick) {
+                Some((_, xy)) => xy[half] = scaled,
+                None => {
+                    let mut xy = [0.5f32; 2];
+                    xy[half] = scaled;
+                    sticks.push((stick, xy));
+                }
+            },
+            (trigger, None) => {
+                let command = format!("SET {} {:.4}", trigger, value.clamp(0f32, 1f32));
+                commands.push((trigger, command));
+            }
+        }
+    }
+    for (stick, [x, y]) in sticks {
+        let command = format!("SET {} {:.4} {:.4}", stick, x, y);
+        commands.push((stick, command));
+    }
+
+    for (control, pressed) in layout.buttons.iter().zip(frame.buttons.iter()) {
+        let name = match button_name(control) {
+            Some(name) => name,
+            None => continue,
+        };
+        let verb = if *pressed { "PRESS" } else { "RELEASE" };
+        commands.push((name.clone(), format!("{} {}", verb, name)));
+    }
+
+    commands
+}
+
+// Writes a team's frames to a FIFO that Dolphin reads as a controller. Only
+// changes are sent, and everything again whenever Dolphin (re)opens the pipe.
+pub struct DolphinPipe {
+    path: PathBuf,
+    file: Option<File>,
+    sent: HashMap<String, String>,
+}
+
+impl DolphinPipe {
+    pub fn new(dir: &str, name: &str) -> Self {
+        std::fs::create_dir_all(dir).expect("Failed to create the Dolphin pipe directory");
+        let path = Path::new(dir).join(name);
+        if !path.exists() {
+            nix::unistd::mkfifo(
+                &path,
+                nix::sys::stat::Mode::S_IRUSR | nix::sys::stat::Mode::S_IWUSR,
+            )
+            .expect("Failed to create the Dolphin pipe");
+        }
+        println!("Writing to Dolphin pipe {}", path.display());
+        DolphinPipe {
+            path,
+            file: None,
+            sent: HashMap::new(),
+        }
+    }
+
+    // Opening without a reader fails until Dolphin has the pipe open
+    fn open(&mut self) -> Option<&mut File> {
+        if self.file.is_none() {
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .custom_flags(nix::libc::O_NONBLOCK)
+                .open(&self.path)
+                .ok()?;
+            self.file = Some(file);
+            self.sent.clear();
+        }
+        self.file.as_mut()
+    }
+}
+
+impl Output for DolphinPipe {
+    fn write(&mut self, layout: &Layout, frame: &Frame) {
+        let mut text = String::new();
+        let commands = commands(layout, frame);
+        if self.open().is_none() {
+            return;
+        }
+        for (target, command) in commands {
+            if self.sent.get(&target) != Some(&command) {
+                text.push_str(&command);
+                text.push('\n');
+                self.sent.insert(target, command);
+            }
+        }
+        if text.is_empty() {
+            return;
+        }
+
+        let file = self.file.as_mut().unwrap();
+        if file.write_all(text.as_bytes()).is_err() {
+            // Dolphin went away, or isn't keeping up
+            self.file = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamecube_commands() {
+        let layout = Layout::load("../resources/layouts/gamecube.json");
+        let mut frame = Frame {
+            axes: vec![1f32, 0f32, -0.5f32, 0f32],
+            buttons: vec![false; layout.buttons.len()],
+        };
+        frame.buttons[0] = true;
+
+        let commands: Vec<String> = commands(&layout, &frame)
+            .into_iter()
+            .map(|(_, command)| command)
+            .collect();
+        assert_eq!(commands[0], "SET MAIN 1.0000 0.5000");
+        assert_eq!(commands[1], "SET C 0.2500 0.5000");
+        assert_eq!(commands[2], "PRESS A");
+        assert!(commands.contains(&"RELEASE START".to_string()));
+    }
+
+    #[test]
+    fn only_changes_are_written() {
+        let layout = Layout::load("../resources/layouts/snes.json");
+        let dir = std::env::temp_dir().join(format!("mjoy-dolphin-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // A plain file stands in for the FIFO, which would need a reader
+        std::fs::write(dir.join("Buster0"), "").unwrap();
+        let mut pipe = DolphinPipe::new(dir.to_str().unwrap(), "Buster0");
+
+        let mut frame = Frame {
+            axes: vec![0f32, 0f32],
+            buttons: vec![false; layout.buttons.len()],
+        };
+        pipe.write(&layout, &frame);
+        frame.buttons[0] = true;
+        pipe.write(&layout, &frame);
+        pipe.write(&layout, &frame);
+
+        let written = std::fs::read_to_string(dir.join("Buster0")).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        // Select has no Dolphin button, so it is left out
+        assert_eq!(lines.len(), 1 + 7 + 1);
+        assert_eq!(lines[0], "SET MAIN 0.5000 0.5000");
+        assert_eq!(lines.last(), Some(&"PRESS A"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
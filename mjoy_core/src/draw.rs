@@ -0,0 +1,283 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[clap(rename_all = "snake_case")]
+pub enum Strategy {
+    // Teams fill up in port order
+    #[default]
+    PortOrder,
+    Shuffle,
+    // Players are taken best rated first, team 1 to N and back again
+    SnakeDraft,
+    // Swaps players until team ratings are as even as they get
+    Balanced,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DrawConfig {
+    #[serde(default)]
+    pub strategy: Strategy,
+    // Random when unset. Shuffles and breaks rating ties.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // By player common name. Unrated players count as the average.
+    #[serde(default)]
+    pub ratings: HashMap<String, f32>,
+    // Groups of players to put on different teams
+    #[serde(default)]
+    pub keep_apart: Vec<Vec<String>>,
+    // Groups of players to put on the same team
+    #[serde(default)]
+    pub keep_together: Vec<Vec<String>>,
+}
+
+pub struct Draw {
+    pub teams: Vec<Vec<String>>,
+    // None for port order, which has nothing to reproduce
+    pub seed: Option<u64>,
+}
+
+// Breaking a constraint outweighs any rating imbalance
+const VIOLATION_COST: f32 = 1_000_000f32;
+
+struct Assignment<'a> {
+    config: &'a DrawConfig,
+    players: &'a [String],
+    ratings: Vec<f32>,
+    capacities: &'a [u32],
+    // Team index per player
+    teams: Vec<usize>,
+}
+
+impl<'a> Assignment<'a> {
+    fn pairs(&self, groups: &[Vec<String>]) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for group in groups.iter() {
+            let members: Vec<usize> = group
+                .iter()
+                .filter_map(|name| self.players.iter().position(|p| p == name))
+                .collect();
+            for (i, a) in members.iter().enumerate() {
+                for b in members[i + 1..].iter() {
+                    pairs.push((*a, *b));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn violations(&self) -> usize {
+        let apart = self
+            .pairs(&self.config.keep_apart)
+            .into_iter()
+            .filter(|(a, b)| self.teams[*a] == self.teams[*b]);
+        let together = self
+            .pairs(&self.config.keep_together)
+            .into_iter()
+            .filter(|(a, b)| self.teams[*a] != self.teams[*b]);
+        apart.count() + together.count()
+    }
+
+    // Variance of the teams' mean ratings
+    fn imbalance(&self) -> f32 {
+        let means: Vec<f32> = (0..self.capacities.len())
+            .map(|team| {
+                let ratings: Vec<f32> = (0..self.players.len())
+                    .filter(|p| self.teams[*p] == team)
+                    .map(|p| self.ratings[p])
+                    .collect();
+                ratings.iter().sum::<f32>() / ratings.len().max(1) as f32
+            })
+            .collect();
+        let mean = means.iter().sum::<f32>() / means.len().max(1) as f32;
+        means.iter().map(|m| (m - mean).powi(2)).sum()
+    }
+
+    fn cost(&self, balance: bool) -> f32 {
+        let cost = self.violations() as f32 * VIOLATION_COST;
+        if balance {
+            cost + self.imbalance()
+        } else {
+            cost
+        }
+    }
+
+    // Takes the best swap of two players on different teams until none helps
+    fn improve(&mut self, balance: bool) {
+        let mut cost = self.cost(balance);
+        loop {
+            let mut best = None;
+            for a in 0..self.players.len() {
+                for b in a + 1..self.players.len() {
+                    if self.teams[a] == self.teams[b] {
+                        continue;
+                    }
+                    self.teams.swap(a, b);
+                    let swapped = self.cost(balance);
+                    self.teams.swap(a, b);
+                    if swapped < cost - f32::EPSILON {
+                        cost = swapped;
+                        best = Some((a, b));
+                    }
+                }
+            }
+            match best {
+                Some((a, b)) => self.teams.swap(a, b),
+                None => break,
+            }
+        }
+    }
+
+    fn fill(&mut self, order: &[usize]) {
+        let mut slots = Vec::new();
+        for (team, capacity) in self.capacities.iter().enumerate() {
+            slots.extend(std::iter::repeat_n(team, *capacity as usize));
+        }
+        for (player, team) in order.iter().zip(slots) {
+            self.teams[*player] = team;
+        }
+    }
+
+    fn snake(&mut self, order: &[usize]) {
+        let mut room: Vec<u32> = self.capacities.to_vec();
+        let mut order = order.iter();
+        let mut forward = true;
+        while room.iter().any(|r| *r > 0) {
+            let mut teams: Vec<usize> = (0..room.len()).collect();
+            if !forward {
+                teams.reverse();
+            }
+            for team in teams {
+                if room[team] == 0 {
+                    continue;
+                }
+                match order.next() {
+                    Some(player) => self.teams[*player] = team,
+                    None => return,
+                }
+                room[team] -= 1;
+            }
+            forward = !forward;
+        }
+    }
+}
+
+// Splits players, given in port order, into teams of the allocated sizes
+pub fn draw(config: &DrawConfig, players: &[String], capacities: &[u32]) -> Draw {
+    let seed = match (config.strategy, config.seed) {
+        (Strategy::PortOrder, _) => None,
+        (_, Some(seed)) => Some(seed),
+        (_, None) => Some(rand::random::<u64>()),
+    };
+    let mut rng = StdRng::seed_from_u64(seed.unwrap_or_default());
+
+    let rated: Vec<f32> = players
+        .iter()
+        .filter_map(|p| config.ratings.get(p).copied())
+        .collect();
+    let average = rated.iter().sum::<f32>() / rated.len().max(1) as f32;
+    let mut assignment = Assignment {
+        config,
+        players,
+        ratings: players
+            .iter()
+            .map(|p| *config.ratings.get(p).unwrap_or(&average))
+            .collect(),
+        capacities,
+        teams: vec![0; players.len()],
+    };
+
+    let mut order: Vec<usize> = (0..players.len()).collect();
+    if config.strategy != Strategy::PortOrder {
+        order.shuffle(&mut rng);
+    }
+    match config.strategy {
+        Strategy::PortOrder | Strategy::Shuffle => assignment.fill(&order),
+        Strategy::SnakeDraft | Strategy::Balanced => {
+            // Stable, so equal ratings keep their shuffled order
+            order.sort_by(|a, b| {
+                assignment.ratings[*b]
+                    .partial_cmp(&assignment.ratings[*a])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            assignment.snake(&order);
+        }
+    }
+    assignment.improve(config.strategy == Strategy::Balanced);
+    if assignment.violations() > 0 {
+        eprintln!("Could not keep every group of players apart or together");
+    }
+
+    let teams = (0..capacities.len())
+        .map(|team| {
+            (0..players.len())
+                .filter(|p| assignment.teams[*p] == team)
+                .map(|p| players[p].clone())
+                .collect()
+        })
+        .collect();
+    Draw { teams, seed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn players(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("p{}", i)).collect()
+    }
+
+    #[test]
+    fn port_order_and_constraints() {
+        let players = players(4);
+        let config = DrawConfig::default();
+        let draw = draw(&config, &players, &[2, 2]);
+        assert_eq!(draw.seed, None);
+        assert_eq!(draw.teams, [vec!["p0", "p1"], vec!["p2", "p3"]]);
+
+        let json = r#"{
+            "keep_apart": [["p0", "p1"]],
+            "keep_together": [["p1", "p3"]]
+        }"#;
+        let config: DrawConfig = serde_json::from_str(json).unwrap();
+        let draw = super::draw(&config, &players, &[2, 2]);
+        assert_eq!(draw.teams, [vec!["p1", "p3"], vec!["p0", "p2"]]);
+    }
+
+    #[test]
+    fn seeded_shuffle_is_reproducible() {
+        let players = players(6);
+        let json = r#"{"strategy": "shuffle", "seed": 7}"#;
+        let config: DrawConfig = serde_json::from_str(json).unwrap();
+        let first = draw(&config, &players, &[3, 3]);
+        let second = draw(&config, &players, &[3, 3]);
+        assert_eq!(first.seed, Some(7));
+        assert_eq!(first.teams, second.teams);
+        assert_eq!(first.teams[0].len(), 3);
+    }
+
+    #[test]
+    fn snake_draft_and_balance() {
+        let players = players(4);
+        let json = r#"{
+            "strategy": "snake_draft",
+            "seed": 1,
+            "ratings": {"p0": 10, "p1": 40, "p2": 30, "p3": 20}
+        }"#;
+        let mut config: DrawConfig = serde_json::from_str(json).unwrap();
+        // 40 and 30 go to teams 1 and 2, then 20 and 10 come back the other way
+        let draw = draw(&config, &players, &[2, 2]);
+        assert_eq!(draw.teams, [vec!["p0", "p1"], vec!["p2", "p3"]]);
+
+        // Uneven sizes: the lone player should be the one nearest the average
+        config.strategy = Strategy::Balanced;
+        let draw = super::draw(&config, &players, &[3, 1]);
+        assert_eq!(draw.teams[1].len(), 1);
+        assert!(draw.teams[1] == ["p2"] || draw.teams[1] == ["p3"]);
+    }
+}
@@ -0,0 +1,125 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// Every field that is set has to match. Ids are hex, name and path are regexes.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RuleConfig {
+    #[serde(default, with = "crate::hex_id")]
+    pub vendor_id: Option<u16>,
+    #[serde(default, with = "crate::hex_id")]
+    pub product_id: Option<u16>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FilterConfig {
+    // When empty every device is allowed
+    #[serde(default)]
+    pub allow: Vec<RuleConfig>,
+    #[serde(default)]
+    pub deny: Vec<RuleConfig>,
+    // uinput devices, including the joysticks we create ourselves
+    #[serde(default)]
+    pub allow_virtual: bool,
+}
+
+pub struct DeviceInfo<'a> {
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub name: &'a str,
+    pub path: &'a str,
+}
+
+struct Rule {
+    vendor_id: Option<u16>,
+    product_id: Option<u16>,
+    name: Option<Regex>,
+    path: Option<Regex>,
+}
+
+fn regex(pattern: &Option<String>) -> Option<Regex> {
+    pattern
+        .as_ref()
+        .map(|p| Regex::new(p).expect("Invalid device filter regex"))
+}
+
+impl Rule {
+    fn new(config: &RuleConfig) -> Self {
+        Rule {
+            vendor_id: config.vendor_id,
+            product_id: config.product_id,
+            name: regex(&config.name),
+            path: regex(&config.path),
+        }
+    }
+
+    fn matches(&self, device: &DeviceInfo) -> bool {
+        let id = |wanted: Option<u16>, actual: Option<u16>| match wanted {
+            Some(wanted) => actual == Some(wanted),
+            None => true,
+        };
+        let re = |wanted: &Option<Regex>, actual: &str| match wanted {
+            Some(wanted) => wanted.is_match(actual),
+            None => true,
+        };
+        id(self.vendor_id, device.vendor_id)
+            && id(self.product_id, device.product_id)
+            && re(&self.name, device.name)
+            && re(&self.path, device.path)
+    }
+}
+
+pub struct DeviceFilter {
+    allow: Vec<Rule>,
+    deny: Vec<Rule>,
+    allow_virtual: bool,
+}
+
+impl DeviceFilter {
+    pub fn new(config: &FilterConfig) -> Self {
+        DeviceFilter {
+            allow: config.allow.iter().map(Rule::new).collect(),
+            deny: config.deny.iter().map(Rule::new).collect(),
+            allow_virtual: config.allow_virtual,
+        }
+    }
+
+    pub fn allow_virtual(&self) -> bool {
+        self.allow_virtual
+    }
+
+    pub fn accepts(&self, device: &DeviceInfo) -> bool {
+        let allowed = self.allow.is_empty() || self.allow.iter().any(|r| r.matches(device));
+        allowed && !self.deny.iter().any(|r| r.matches(device))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_and_deny() {
+        let json = r#"{
+            "allow": [{"vendor_id": "0079"}, {"name": "Xbox"}],
+            "deny": [{"path": "usb-0:4:"}]
+        }"#;
+        let config: FilterConfig = serde_json::from_str(json).unwrap();
+        let filter = DeviceFilter::new(&config);
+        let device = |vendor_id, name, path| DeviceInfo {
+            vendor_id: Some(vendor_id),
+            product_id: Some(0x0006),
+            name,
+            path,
+        };
+
+        assert!(filter.accepts(&device(0x0079, "Mayflash", "pci-usb-0:1:1.0")));
+        assert!(filter.accepts(&device(0x045e, "Microsoft Xbox Controller", "usb-0:2:1.0")));
+        assert!(!filter.accepts(&device(0x28de, "Steam Deck", "usb-0:3:1.0")));
+        assert!(!filter.accepts(&device(0x0079, "Mayflash", "pci-usb-0:4:1.0")));
+        assert!(!filter.allow_virtual());
+    }
+}
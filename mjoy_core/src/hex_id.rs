@@ -0,0 +1,49 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+// USB vendor and product ids are written as four hex digits, like "045e"
+pub fn parse(id: &str) -> Option<u16> {
+    u16::from_str_radix(id, 16).ok()
+}
+
+// For optional id fields in the config, so a typo fails the load instead of
+// quietly matching nothing
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u16>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(id) => match parse(&id) {
+            Some(id) => Ok(Some(id)),
+            None => Err(de::Error::custom(format!(
+                "\"{}\" is not a hex id, like \"045e\"",
+                id
+            ))),
+        },
+        None => Ok(None),
+    }
+}
+
+pub fn serialize<S: Serializer>(id: &Option<u16>, serializer: S) -> Result<S::Ok, S::Error> {
+    id.map(|id| format!("{:04x}", id)).serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    #[derive(serde::Deserialize, serde::Serialize)]
+    struct Ids {
+        #[serde(default, with = "super")]
+        vendor_id: Option<u16>,
+    }
+
+    #[test]
+    fn hex_ids() {
+        let ids: Ids = serde_json::from_str(r#"{"vendor_id": "045E"}"#).unwrap();
+        assert_eq!(ids.vendor_id, Some(0x045e));
+        assert_eq!(
+            serde_json::to_string(&ids).unwrap(),
+            r#"{"vendor_id":"045e"}"#
+        );
+
+        let ids: Ids = serde_json::from_str("{}").unwrap();
+        assert_eq!(ids.vendor_id, None);
+        assert!(serde_json::from_str::<Ids>(r#"{"vendor_id": "0x45e"}"#).is_err());
+        assert!(serde_json::from_str::<Ids>(r#"{"vendor_id": "12345"}"#).is_err());
+    }
+}
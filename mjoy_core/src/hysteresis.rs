@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+// The bands move the threshold, so they do nothing for the majority, unanimous
+// and any aggregations, which count pressed players instead. min_hold_ms applies
+// to every aggregation.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Hysteresis {
+    // Added to the threshold while released, so a press needs a clear majority
+    #[serde(default)]
+    pub press_band: f32,
+    // Taken off the threshold while pressed, so a press isn't dropped on a wobble
+    #[serde(default)]
+    pub release_band: f32,
+    // A press or release is held at least this long before it can flip again
+    #[serde(default)]
+    pub min_hold_ms: u64,
+}
+
+impl Hysteresis {
+    pub fn has_bands(&self) -> bool {
+        self.press_band != 0f32 || self.release_band != 0f32
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ButtonLatch {
+    pressed: bool,
+    changed: Option<Instant>,
+}
+
+impl ButtonLatch {
+    // `decide` answers whether the button is pressed against the given threshold
+    pub fn update(
+        &mut self,
+        hysteresis: &Hysteresis,
+        threshold: f32,
+        now: Instant,
+        decide: impl FnOnce(f32) -> bool,
+    ) -> bool {
+        let cut = if self.pressed {
+            threshold - hysteresis.release_band
+        } else {
+            threshold + hysteresis.press_band
+        };
+        let wanted = decide(cut);
+        if wanted == self.pressed {
+            return self.pressed;
+        }
+
+        let held = match self.changed {
+            Some(changed) => now.duration_since(changed),
+            None => Duration::MAX,
+        };
+        if held >= Duration::from_millis(hysteresis.min_hold_ms) {
+            self.pressed = wanted;
+            self.changed = Some(now);
+        }
+        self.pressed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bands_and_hold() {
+        let hysteresis = Hysteresis {
+            press_band: 0.1,
+            release_band: 0.1,
+            min_hold_ms: 100,
+        };
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut latch = ButtonLatch::default();
+
+        assert!(!latch.update(&hysteresis, 0.5, at(0), |cut| 0.55 > cut));
+        assert!(latch.update(&hysteresis, 0.5, at(0), |cut| 0.65 > cut));
+        // Inside the release band, then too soon to release
+        assert!(latch.update(&hysteresis, 0.5, at(10), |cut| 0.45 > cut));
+        assert!(latch.update(&hysteresis, 0.5, at(50), |cut| 0.1 > cut));
+        assert!(!latch.update(&hysteresis, 0.5, at(100), |cut| 0.1 > cut));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::joypaths::InputDevice;
+use serde::{Deserialize, Serialize};
+
+// A device the user has given a fixed player name. Every key that is set has to match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredDevice {
+    pub name: String,
+    #[serde(default)]
+    pub serial: Option<String>,
+    #[serde(default)]
+    pub uniq: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+impl RegisteredDevice {
+    fn matches(&self, device: &InputDevice) -> bool {
+        let keys = [
+            (&self.serial, device.serial().map(|s| s.to_string())),
+            (&self.uniq, device.uniq()),
+            (&self.path, Some(device.minimal_path())),
+        ];
+        let mut any = false;
+        for (wanted, actual) in keys.iter() {
+            if let Some(wanted) = wanted {
+                if actual.as_ref() != Some(wanted) {
+                    return false;
+                }
+                any = true;
+            }
+        }
+        any
+    }
+}
+
+// What a player's name is derived from. Anything that a device doesn't report
+// falls back to the port path, which is what names were always based on.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum Identity {
+    #[default]
+    PortPath,
+    Serial,
+    // Bluetooth pads report their MAC address here
+    Uniq,
+    Registry {
+        devices: Vec<RegisteredDevice>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceKey {
+    // Hashed into a word name
+    Hashed(String),
+    // Used as the player name as is
+    Named(String),
+}
+
+impl Identity {
+    pub fn key(&self, device: &InputDevice) -> DeviceKey {
+        let port_path = || DeviceKey::Hashed(device.minimal_path());
+        match self {
+            Identity::PortPath => port_path(),
+            Identity::Serial => match device.serial() {
+                Some(serial) => DeviceKey::Hashed(format!("serial:{}", serial)),
+                None => port_path(),
+            },
+            Identity::Uniq => match device.uniq() {
+                Some(uniq) => DeviceKey::Hashed(format!("uniq:{}", uniq)),
+                None => port_path(),
+            },
+            Identity::Registry { devices } => match devices.iter().find(|d| d.matches(device)) {
+                Some(registered) => DeviceKey::Named(registered.name.clone()),
+                None => port_path(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let device = InputDevice::fake(
+            "/dev/input/event3",
+            &[
+                ("ID_PATH", "pci-0000:00:14.0-usb-0:3:1.0"),
+                ("ID_SERIAL_SHORT", "ABC123"),
+            ],
+        );
+
+        assert_eq!(
+            Identity::PortPath.key(&device),
+            DeviceKey::Hashed("3:1.0".to_string())
+        );
+        assert_eq!(
+            Identity::Serial.key(&device),
+            DeviceKey::Hashed("serial:ABC123".to_string())
+        );
+        assert_eq!(
+            Identity::Uniq.key(&device),
+            DeviceKey::Hashed("3:1.0".to_string())
+        );
+
+        let json = r#"{"strategy": "registry", "devices": [
+            {"name": "kevin", "serial": "ABC123"},
+            {"name": "nobody", "serial": "ABC123", "path": "9:1.0"}
+        ]}"#;
+        let registry: Identity = serde_json::from_str(json).unwrap();
+        assert_eq!(registry.key(&device), DeviceKey::Named("kevin".to_string()));
+    }
+}
@@ -1,43 +1,117 @@
-use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
-
-#[derive(Debug, EnumIter)]
-pub enum NamedButton {
-    A,
-    B,
-    X,
-    Y,
-    L,
-    R,
-    Select,
-    Start,
-}
-
-#[derive(Debug, EnumIter)]
-pub enum NamedAxis {
-    Xright,
-    Yup,
-}
-
-pub fn snes_namedaxis_to_id_and_scalar(a: &NamedAxis) -> (u32, f32) {
-    use NamedAxis::*;
-    match a {
-        Xright => (0, 32767.0),
-        Yup => (1, -32767.0),
+use crate::input::InputSource;
+use crate::layout::{AxisControl, ButtonControl};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AxisSource {
+    #[default]
+    Dpad,
+    Analog,
+}
+
+fn default_deadzone() -> f32 {
+    0.15
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AxisSources {
+    #[serde(default)]
+    pub default: AxisSource,
+    // Keyed by a substring of the gilrs gamepad name, e.g. "MAYFLASH"
+    #[serde(default)]
+    pub models: HashMap<String, AxisSource>,
+    #[serde(default = "default_deadzone")]
+    pub deadzone: f32,
+}
+
+impl Default for AxisSources {
+    fn default() -> Self {
+        AxisSources {
+            default: AxisSource::default(),
+            models: HashMap::new(),
+            deadzone: default_deadzone(),
+        }
+    }
+}
+
+impl AxisSources {
+    pub fn for_model(&self, name: &str) -> AxisSource {
+        for (model, source) in self.models.iter() {
+            if name.contains(model.as_str()) {
+                return *source;
+            }
+        }
+        self.default
+    }
+}
+
+fn dpad_value(input: &dyn InputSource, player: &str, a: &AxisControl) -> f32 {
+    let dpad = match &a.dpad {
+        Some(dpad) => dpad,
+        None => return 0f32,
+    };
+    let value = match input.button(player, dpad.button) {
+        Some(value) => match value {
+            v if v < 0.1 => -1f32,
+            v if v > 0.9 => 1f32,
+            _ => 0f32,
+        },
+        None => 0f32,
+    };
+    value * dpad.scalar
+}
+
+fn analog_value(input: &dyn InputSource, player: &str, a: &AxisControl, deadzone: f32) -> f32 {
+    let stick = match &a.stick {
+        Some(stick) => stick,
+        None => return 0f32,
+    };
+    let value = input.axis(player, stick.axis).unwrap_or(0f32);
+    let magnitude = value.abs();
+    if magnitude <= deadzone || deadzone >= 1f32 {
+        return 0f32;
+    }
+    let magnitude = ((magnitude - deadzone) / (1f32 - deadzone)).min(1f32);
+    value.signum() * magnitude * stick.scalar
+}
+
+// Where a player's value for an axis comes from, falling back to whichever
+// input the layout has for it
+pub fn axis_source(
+    input: &dyn InputSource,
+    player: &str,
+    a: &AxisControl,
+    sources: &AxisSources,
+) -> AxisSource {
+    let model = input.model(player).unwrap_or_default();
+    match (sources.for_model(&model), &a.dpad, &a.stick) {
+        (AxisSource::Dpad, None, Some(_)) => AxisSource::Analog,
+        (AxisSource::Analog, Some(_), None) => AxisSource::Dpad,
+        (source, _, _) => source,
+    }
+}
+
+pub fn axis_value(
+    input: &dyn InputSource,
+    player: &str,
+    a: &AxisControl,
+    sources: &AxisSources,
+) -> f32 {
+    match axis_source(input, player, a, sources) {
+        AxisSource::Dpad => dpad_value(input, player, a),
+        AxisSource::Analog => analog_value(input, player, a, sources.deadzone),
     }
 }
 
-pub fn snes_namedbutton_to_id(b: &NamedButton) -> gilrs::Button {
-    use gilrs::Button;
-    use NamedButton::*;
-    match b {
-        X => Button::North,
-        A => Button::East,
-        B => Button::South,
-        Y => Button::West,
-        Start => Button::Start,
-        Select => Button::Select,
-        L => Button::LeftTrigger,
-        R => Button::RightTrigger,
+pub fn button_value(input: &dyn InputSource, player: &str, b: &ButtonControl) -> f32 {
+    match input.button(player, b.input) {
+        Some(value) => match value {
+            v if v < 0.1 => 0f32,
+            v if v > 0.9 => 1f32,
+            _ => 0f32,
+        },
+        None => 0f32,
     }
 }
@@ -0,0 +1,435 @@
+use crate::filter::{DeviceFilter, DeviceInfo};
+use crate::joypaths::{self, EventPathLookup, NamedPath};
+use crate::rumble::Rumble;
+use crate::Config;
+use gilrs::ff::{BaseEffect, BaseEffectType, EffectBuilder, Repeat, Replay, Ticks};
+use gilrs::{Axis, Button, GamepadId};
+use mjoy_gui::wordhash::Wordhash;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Where player controller state comes from. Players are addressed by common name.
+pub trait InputSource {
+    // Drains pending events. True when controllers came or went, in which case
+    // names are fresh and any earlier `rename` has to be applied again.
+    fn poll(&mut self, now: Instant) -> bool;
+    // Connected controllers, as seen by discovery, for building a teamlock
+    fn named_paths(&self) -> Vec<NamedPath>;
+    fn players(&self) -> Vec<String>;
+    fn connected(&self, player: &str) -> bool;
+    // Controller name -> player slot it now answers to
+    fn rename(&mut self, aliases: &HashMap<String, String>);
+    fn model(&self, player: &str) -> Option<String>;
+    fn button(&self, player: &str, button: Button) -> Option<f32>;
+    fn axis(&self, player: &str, axis: Axis) -> Option<f32>;
+    // Replaces whatever these players were last rumbled with
+    fn rumble(&mut self, players: &[String], rumble: &Rumble);
+}
+
+// Everyone still connected holds West and the D-pad left, which these pads
+// report as DPadRight near 0 (0.5 is centred). With nobody connected there is
+// nobody to wait for.
+pub fn start_gesture_held(input: &dyn InputSource, players: &[String]) -> bool {
+    players.iter().filter(|p| input.connected(p)).all(|player| {
+        let west = input.button(player, Button::West);
+        let right = input.button(player, Button::DPadRight);
+        match (west, right) {
+            (Some(west), Some(right)) => west > 0.5 && right < 0.1,
+            _ => false,
+        }
+    })
+}
+
+pub struct GilrsInput<'a> {
+    gilrs: gilrs::Gilrs,
+    words: &'a Wordhash,
+    config: &'a Config,
+    filter: DeviceFilter,
+    lookup: EventPathLookup,
+    ids: HashMap<String, GamepadId>,
+    // Playing effects, keyed by the players they were played on. Dropping one stops it.
+    effects: HashMap<Vec<String>, gilrs::ff::Effect>,
+}
+
+impl<'a> GilrsInput<'a> {
+    pub fn new(words: &'a Wordhash, config: &'a Config) -> Self {
+        let gilrs = gilrs::Gilrs::new().unwrap();
+
+        for (_id, gamepad) in gilrs.gamepads() {
+            println!(
+                "{} is {:?} {}",
+                gamepad.name(),
+                gamepad.power_info(),
+                gamepad.devpath()
+            );
+        }
+
+        let mut input = GilrsInput {
+            gilrs,
+            words,
+            config,
+            filter: DeviceFilter::new(&config.device_filter),
+            lookup: EventPathLookup(HashMap::new()),
+            ids: HashMap::new(),
+            effects: HashMap::new(),
+        };
+        input.repath();
+        input
+    }
+
+    fn repath(&mut self) {
+        self.lookup = joypaths::repath_joys(self.words, self.config)
+            .unwrap()
+            .into();
+        self.reindex();
+    }
+
+    fn reindex(&mut self) {
+        self.ids.clear();
+        for (id, gamepad) in self.gilrs.gamepads() {
+            let namedpath = match self.lookup.0.get(gamepad.devpath()) {
+                Some(namedpath) => namedpath,
+                None => continue,
+            };
+            let accepted = self.filter.accepts(&DeviceInfo {
+                vendor_id: namedpath.vendor_id,
+                product_id: namedpath.product_id,
+                name: gamepad.os_name(),
+                path: &namedpath.full_path,
+            });
+            if !accepted {
+                continue;
+            }
+            if let Some(other) = self.ids.get(&namedpath.common_name) {
+                eprintln!(
+                    "{} and {} are both called {}, ignoring {}",
+                    self.gilrs.gamepad(*other).devpath(),
+                    gamepad.devpath(),
+                    namedpath.common_name,
+                    gamepad.devpath()
+                );
+                continue;
+            }
+            self.ids.insert(namedpath.common_name.clone(), id);
+        }
+    }
+
+    fn gamepad(&self, player: &str) -> Option<gilrs::Gamepad<'_>> {
+        self.gilrs.connected_gamepad(*self.ids.get(player)?)
+    }
+}
+
+impl<'a> InputSource for GilrsInput<'a> {
+    fn poll(&mut self, _now: Instant) -> bool {
+        let mut repath = false;
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                gilrs::EventType::Connected | gilrs::EventType::Disconnected => repath = true,
+                _ => {}
+            }
+        }
+        if repath {
+            self.repath();
+        }
+        repath
+    }
+
+    fn named_paths(&self) -> Vec<NamedPath> {
+        joypaths::repath_joys(self.words, self.config).unwrap()
+    }
+
+    fn players(&self) -> Vec<String> {
+        self.ids.keys().cloned().collect()
+    }
+
+    fn connected(&self, player: &str) -> bool {
+        self.gamepad(player).is_some()
+    }
+
+    fn rename(&mut self, aliases: &HashMap<String, String>) {
+        for np in self.lookup.0.values_mut() {
+            if let Some(slot) = aliases.get(&np.common_name) {
+                np.common_name = slot.clone();
+            }
+        }
+        self.reindex();
+    }
+
+    fn model(&self, player: &str) -> Option<String> {
+        Some(self.gamepad(player)?.name().to_string())
+    }
+
+    fn button(&self, player: &str, button: Button) -> Option<f32> {
+        Some(self.gamepad(player)?.button_data(button)?.value())
+    }
+
+    fn axis(&self, player: &str, axis: Axis) -> Option<f32> {
+        Some(self.gamepad(player)?.axis_data(axis)?.value())
+    }
+
+    fn rumble(&mut self, players: &[String], rumble: &Rumble) {
+        self.effects.remove(players);
+        if rumble.is_stop() {
+            return;
+        }
+        let ids: Vec<GamepadId> = players
+            .iter()
+            .filter(|p| self.gamepad(p).is_some_and(|g| g.is_ff_supported()))
+            .map(|p| self.ids[p])
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+
+        // Effects without a length keep going until they are replaced
+        let (play_for, repeat) = match rumble.duration {
+            Some(d) => {
+                let ticks = Ticks::from_ms(d.as_millis().min(u32::MAX as u128) as u32);
+                (ticks, Repeat::For(ticks))
+            }
+            None => (Ticks::from_ms(50), Repeat::Infinitely),
+        };
+        let mut builder = EffectBuilder::new();
+        for kind in [
+            BaseEffectType::Strong {
+                magnitude: (rumble.strong * u16::MAX as f32) as u16,
+            },
+            BaseEffectType::Weak {
+                magnitude: (rumble.weak * u16::MAX as f32) as u16,
+            },
+        ] {
+            builder.add_effect(BaseEffect {
+                kind,
+                scheduling: Replay {
+                    play_for,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        }
+        let effect = builder
+            .gamepads(&ids)
+            .repeat(repeat)
+            .finish(&mut self.gilrs)
+            .and_then(|effect| effect.play().map(|_| effect));
+        match effect {
+            Ok(effect) => {
+                self.effects.insert(players.to_vec(), effect);
+            }
+            Err(e) => eprintln!("Failed to rumble {:?}: {}", players, e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptedPlayer {
+    pub name: String,
+    // Stands in for the gamepad name, for per-model axis sources
+    #[serde(default)]
+    pub model: String,
+}
+
+// Sets one button or axis of a player's pad, or plugs it in or out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptEvent {
+    pub at_ms: u64,
+    pub player: String,
+    #[serde(default)]
+    pub button: Option<Button>,
+    #[serde(default)]
+    pub axis: Option<Axis>,
+    #[serde(default)]
+    pub value: f32,
+    #[serde(default)]
+    pub connected: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timeline {
+    pub players: Vec<ScriptedPlayer>,
+    pub events: Vec<ScriptEvent>,
+}
+
+struct ScriptedPad {
+    model: String,
+    connected: bool,
+    buttons: HashMap<Button, f32>,
+    axes: HashMap<Axis, f32>,
+}
+
+// Plays a timeline of presses back against the clock, in place of real controllers
+pub struct ScriptedInput {
+    events: Vec<ScriptEvent>,
+    next: usize,
+    start: Option<Instant>,
+    pads: Vec<(String, ScriptedPad)>,
+    aliases: HashMap<String, String>,
+}
+
+impl ScriptedInput {
+    pub fn new(timeline: Timeline) -> Self {
+        let mut events = timeline.events;
+        events.sort_by_key(|e| e.at_ms);
+        let pads = timeline
+            .players
+            .into_iter()
+            .map(|p| {
+                let pad = ScriptedPad {
+                    model: p.model,
+                    connected: true,
+                    buttons: HashMap::new(),
+                    axes: HashMap::new(),
+                };
+                (p.name, pad)
+            })
+            .collect();
+        ScriptedInput {
+            events,
+            next: 0,
+            start: None,
+            pads,
+            aliases: HashMap::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> Self {
+        let text = std::fs::read_to_string(path).expect("Failed to read input script");
+        let timeline = serde_json::from_str(&text).expect("Failed to parse input script");
+        ScriptedInput::new(timeline)
+    }
+
+    fn exposed_name<'n>(&'n self, name: &'n str) -> &'n str {
+        self.aliases.get(name).map(|s| s.as_str()).unwrap_or(name)
+    }
+
+    fn pad(&self, player: &str) -> Option<&ScriptedPad> {
+        self.pads
+            .iter()
+            .find(|(name, pad)| pad.connected && self.exposed_name(name) == player)
+            .map(|(_, pad)| pad)
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll(&mut self, now: Instant) -> bool {
+        let start = *self.start.get_or_insert(now);
+        let elapsed = now.duration_since(start);
+        let mut changed = false;
+        while let Some(event) = self.events.get(self.next) {
+            if Duration::from_millis(event.at_ms) > elapsed {
+                break;
+            }
+            self.next += 1;
+            let pad = match self.pads.iter_mut().find(|(name, _)| *name == event.player) {
+                Some((_, pad)) => pad,
+                None => continue,
+            };
+            if let Some(button) = event.button {
+                pad.buttons.insert(button, event.value);
+            }
+            if let Some(axis) = event.axis {
+                pad.axes.insert(axis, event.value);
+            }
+            if let Some(connected) = event.connected {
+                changed |= pad.connected != connected;
+                pad.connected = connected;
+            }
+        }
+        if changed {
+            self.aliases.clear();
+        }
+        changed
+    }
+
+    fn named_paths(&self) -> Vec<NamedPath> {
+        self.pads
+            .iter()
+            .filter(|(_, pad)| pad.connected)
+            .map(|(name, _)| NamedPath {
+                full_path: format!("script:{}", name),
+                minimal_path: name.clone(),
+                root_event_path: format!("script:{}", name),
+                common_name: name.clone(),
+                vendor_id: None,
+                product_id: None,
+                serial: None,
+            })
+            .collect()
+    }
+
+    fn players(&self) -> Vec<String> {
+        self.pads
+            .iter()
+            .filter(|(_, pad)| pad.connected)
+            .map(|(name, _)| self.exposed_name(name).to_string())
+            .collect()
+    }
+
+    fn connected(&self, player: &str) -> bool {
+        self.pad(player).is_some()
+    }
+
+    fn rename(&mut self, aliases: &HashMap<String, String>) {
+        self.aliases = aliases.clone();
+    }
+
+    fn model(&self, player: &str) -> Option<String> {
+        Some(self.pad(player)?.model.clone())
+    }
+
+    // A connected pad reads every control as released until the script says otherwise
+    fn button(&self, player: &str, button: Button) -> Option<f32> {
+        Some(*self.pad(player)?.buttons.get(&button).unwrap_or(&0f32))
+    }
+
+    fn axis(&self, player: &str, axis: Axis) -> Option<f32> {
+        Some(*self.pad(player)?.axes.get(&axis).unwrap_or(&0f32))
+    }
+
+    // Scripted pads have no motors
+    fn rumble(&mut self, _players: &[String], _rumble: &Rumble) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::injoy;
+    use crate::layout::Layout;
+
+    #[test]
+    fn scripted_timeline() {
+        let mut input = ScriptedInput::load("tests/fixtures/input_script_example.json");
+        let layout = Layout::load("../resources/layouts/snes.json");
+        let players = vec!["ann".to_string(), "bob".to_string()];
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert!(!input.poll(at(0)));
+        let a = &layout.buttons[0];
+        assert_eq!(injoy::button_value(&input, "ann", a), 1f32);
+        assert_eq!(injoy::button_value(&input, "bob", a), 0f32);
+        assert!(!start_gesture_held(&input, &players));
+
+        input.poll(at(100));
+        let x_axis = &layout.axes[0];
+        let sources = injoy::AxisSources {
+            default: injoy::AxisSource::Analog,
+            ..Default::default()
+        };
+        assert_eq!(injoy::axis_value(&input, "bob", x_axis, &sources), 1f32);
+        assert!(start_gesture_held(&input, &players));
+
+        // Bob unplugs, and is no longer asked for the gesture
+        assert!(input.poll(at(200)));
+        assert_eq!(input.players(), ["ann"]);
+        assert_eq!(input.button("bob", Button::West), None);
+        assert!(start_gesture_held(&input, &players));
+
+        // Nobody left to wait for
+        assert!(input.poll(at(300)));
+        assert!(input.players().is_empty());
+        assert!(start_gesture_held(&input, &players));
+        assert!(start_gesture_held(&input, &[]));
+    }
+}
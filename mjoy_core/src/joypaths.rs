@@ -1,5 +1,9 @@
+use crate::adapters;
+use crate::filter::{DeviceFilter, DeviceInfo};
+use crate::identity::DeviceKey;
 use mjoy_gui::wordhash::Wordhash;
 use std::collections::HashMap;
+use std::path::Path;
 
 #[derive(Debug)]
 pub struct NamedPath {
@@ -7,6 +11,23 @@ pub struct NamedPath {
     pub minimal_path: String,
     pub root_event_path: String,
     pub common_name: String,
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub serial: Option<String>,
+}
+
+impl NamedPath {
+    // vendor:product and serial, for the startup listing
+    pub fn describe(&self) -> String {
+        let id = match (self.vendor_id, self.product_id) {
+            (Some(v), Some(p)) => format!("{:04x}:{:04x}", v, p),
+            _ => "????:????".to_string(),
+        };
+        match &self.serial {
+            Some(serial) => format!("{} {}", id, serial),
+            None => id,
+        }
+    }
 }
 
 pub struct EventPathLookup(pub HashMap<String, NamedPath>);
@@ -31,79 +52,467 @@ impl From<Vec<NamedPath>> for MinimalPathLookup {
     }
 }
 
-#[derive(Debug)]
-pub enum RepathError {}
+#[derive(Debug, thiserror::Error)]
+pub enum RepathError {
+    #[error("Failed to open udev")]
+    Udev,
+    #[error("Failed to enumerate input devices through udev")]
+    Enumerate,
+    #[error("Failed to read input devices under {0}")]
+    Root(String),
+}
 
-pub fn repath_joys(
+// A joystick event node as udev reports it
+#[derive(Debug, Clone, Default)]
+pub struct InputDevice {
+    pub devnode: String,
+    pub syspath: String,
+    pub properties: HashMap<String, String>,
+}
+
+impl InputDevice {
+    fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(|v| v.as_str())
+    }
+
+    fn hex_property(&self, key: &str) -> Option<u16> {
+        crate::hex_id::parse(self.property(key)?)
+    }
+
+    pub fn vendor_id(&self) -> Option<u16> {
+        self.hex_property("ID_VENDOR_ID")
+    }
+
+    pub fn product_id(&self) -> Option<u16> {
+        self.hex_property("ID_MODEL_ID")
+    }
+
+    pub fn serial(&self) -> Option<&str> {
+        self.property("ID_SERIAL_SHORT")
+    }
+
+    // uniq and name live on the parent input device rather than in the udev properties
+    fn parent_attribute(&self, attribute: &str) -> Option<String> {
+        let path = format!("{}/device/{}", self.syspath, attribute);
+        let value = std::fs::read_to_string(path).ok()?;
+        let value = value.trim();
+        if value.is_empty() {
+            None
+        } else {
+            Some(value.to_string())
+        }
+    }
+
+    pub fn uniq(&self) -> Option<String> {
+        self.parent_attribute("uniq")
+    }
+
+    pub fn name(&self) -> String {
+        self.parent_attribute("name").unwrap_or_default()
+    }
+
+    pub fn is_virtual(&self) -> bool {
+        self.syspath.contains("/devices/virtual/")
+    }
+
+    // ID_PATH "pci-0000:00:14.0-usb-0:1.2:1.0" gives port "1.2:1" and interface 0
+    fn usb_port_interface(&self) -> Option<(&str, u32)> {
+        let id_path = self.property("ID_PATH")?;
+        let usb = &id_path[id_path.rfind("usb-")? + 4..];
+        let (_bus, rest) = usb.split_once(':')?;
+        let (port, interface) = rest.rsplit_once('.')?;
+        Some((port, interface.parse().ok()?))
+    }
+
+    pub fn usb_port(&self) -> Option<&str> {
+        self.usb_port_interface().map(|(port, _)| port)
+    }
+
+    // Multi-port adapters show up as one USB device with an interface per port
+    pub fn interface(&self) -> u32 {
+        match self.usb_port_interface() {
+            Some((_, interface)) => interface,
+            None => self
+                .property("ID_USB_INTERFACE_NUM")
+                .and_then(|i| u32::from_str_radix(i, 16).ok())
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn minimal_path(&self) -> String {
+        match (self.usb_port_interface(), self.property("ID_PATH")) {
+            (Some((port, interface)), _) => format!("{}.{}", port, interface),
+            (None, Some(id_path)) => id_path.to_string(),
+            (None, None) => self.devnode.clone(),
+        }
+    }
+
+    pub fn full_path(&self) -> String {
+        match self.property("ID_PATH") {
+            Some(id_path) => format!("/dev/input/by-path/{}-event-joystick", id_path),
+            None => self.devnode.clone(),
+        }
+    }
+}
+
+// A device node as either scanner finds it, before discovery decides whether
+// it is a joystick. Parent input devices have no node.
+struct Candidate {
+    devnode: Option<String>,
+    syspath: String,
+    properties: HashMap<String, String>,
+}
+
+// Keeps the joystick event nodes, whichever scanner found them
+fn joysticks(candidates: Vec<Candidate>) -> Vec<InputDevice> {
+    let mut devices: Vec<InputDevice> = candidates
+        .into_iter()
+        .filter(|c| c.properties.get("ID_INPUT_JOYSTICK").map(|v| v.as_str()) == Some("1"))
+        .filter_map(|c| {
+            // The joystick property is also on the parent input device and the legacy js node
+            let devnode = c.devnode?;
+            let node = Path::new(&devnode).file_name()?.to_string_lossy();
+            if !node.starts_with("event") {
+                return None;
+            }
+            Some(InputDevice {
+                devnode,
+                syspath: c.syspath,
+                properties: c.properties,
+            })
+        })
+        .collect();
+    devices.sort_by(|a, b| a.devnode.cmp(&b.devnode));
+    devices
+}
+
+#[cfg(test)]
+impl InputDevice {
+    // A device with these udev properties and nothing in sysfs behind it
+    pub fn fake(devnode: &str, properties: &[(&str, &str)]) -> Self {
+        InputDevice {
+            devnode: devnode.to_string(),
+            syspath: "/nonexistent".to_string(),
+            properties: properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+}
+
+fn udev_candidates() -> Result<Vec<Candidate>, RepathError> {
+    use gilrs::udev::{Device, Udev};
+
+    let udev = Udev::new().ok_or(RepathError::Udev)?;
+    let en = udev.enumerate().ok_or(RepathError::Enumerate)?;
+    en.add_match_property(c"ID_INPUT_JOYSTICK", c"1");
+    en.scan_devices();
+
+    let mut candidates = Vec::new();
+    for syspath in en.iter() {
+        let dev = match Device::from_syspath(&udev, &syspath) {
+            Some(dev) => dev,
+            None => continue,
+        };
+        candidates.push(Candidate {
+            devnode: dev
+                .devnode()
+                .map(|devnode| devnode.to_string_lossy().into_owned()),
+            syspath: dev.syspath().to_string_lossy().into_owned(),
+            properties: dev.properties().collect(),
+        });
+    }
+    Ok(candidates)
+}
+
+// The udev database keeps a device's properties as "E:KEY=value" lines
+fn udev_properties(path: &Path) -> Option<HashMap<String, String>> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let properties = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("E:"))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    Some(properties)
+}
+
+// What udev would report, read straight from sysfs and the udev database under
+// `root`. Lets discovery run against a copy of another machine's tree.
+fn root_candidates(root: &Path) -> Result<Vec<Candidate>, RepathError> {
+    let class = root.join("sys/class/input");
+    let entries =
+        std::fs::read_dir(&class).map_err(|_| RepathError::Root(root.display().to_string()))?;
+
+    let mut candidates = Vec::new();
+    for entry in entries.flatten() {
+        // Unplugged devices leave dangling links, and udev may not have caught up yet
+        let syspath = match std::fs::canonicalize(entry.path()) {
+            Ok(syspath) => syspath,
+            Err(_) => continue,
+        };
+        let dev = match std::fs::read_to_string(syspath.join("dev")) {
+            Ok(dev) => dev,
+            Err(_) => continue,
+        };
+        let data = root.join("run/udev/data").join(format!("c{}", dev.trim()));
+        let properties = match udev_properties(&data) {
+            Some(properties) => properties,
+            None => continue,
+        };
+        candidates.push(Candidate {
+            devnode: Some(
+                root.join("dev/input")
+                    .join(entry.file_name())
+                    .display()
+                    .to_string(),
+            ),
+            syspath: syspath.display().to_string(),
+            properties,
+        });
+    }
+    Ok(candidates)
+}
+
+// Drops what the config says to leave alone
+fn usable_devices(config: &crate::Config, devices: Vec<InputDevice>) -> Vec<InputDevice> {
+    let filter = DeviceFilter::new(&config.device_filter);
+    devices
+        .into_iter()
+        .filter(|d| filter.allow_virtual() || !d.is_virtual())
+        .filter(|d| {
+            filter.accepts(&DeviceInfo {
+                vendor_id: d.vendor_id(),
+                product_id: d.product_id(),
+                name: &d.name(),
+                path: &d.full_path(),
+            })
+        })
+        .filter(|d| match adapters::find(&config.adapters, d) {
+            Some(adapter) => adapter.enabled(d.interface()),
+            None => d.interface() < config.number_of_multi_port_controllers_to_use,
+        })
+        .collect()
+}
+
+pub fn name_devices(
     words: &Wordhash,
     config: &crate::Config,
-) -> Result<Vec<NamedPath>, RepathError> {
-    use regex::Regex;
-    use std::fs;
-
+    devices: Vec<InputDevice>,
+) -> Vec<NamedPath> {
     let mut joy_paths = Vec::new();
 
-    let paths = fs::read_dir("/dev/input/by-path")
-        .expect("I really should be able to read /dev/input/by-path");
-
-    let is_event_joy = Regex::new(r"event-joystick").expect("Compile regex");
-    let path_only = Regex::new(r"/dev/input/by-path/pci.*usb.*:(.*:1)\.([0-9])-event-joystick")
-        .expect("compile regex");
-    let gimme_event = Regex::new(r"../event([0-9]+)").expect("compile regex");
-
-    for path in paths {
-        let path = path.expect("Path conversion failed").path();
-        let full_path = path.to_str().expect("Path tostring failed");
-        if is_event_joy.is_match(&full_path) {
-            let partial_minimal_path = path_only
-                .captures(&full_path)
-                .unwrap()
-                .get(1)
-                .unwrap()
-                .as_str()
-                .to_string();
-            let multi_controller = path_only
-                .captures(&full_path)
-                .unwrap()
-                .get(2)
-                .unwrap()
-                .as_str()
-                .to_string();
-            if config.number_of_multi_port_controllers_to_use
-                <= multi_controller.parse::<u32>().unwrap()
-            {
-                continue;
+    let devices = usable_devices(config, devices);
+    let keys: Vec<DeviceKey> = devices
+        .iter()
+        .map(|d| {
+            let fixed =
+                adapters::find(&config.adapters, d).and_then(|a| a.fixed_name(d.interface()));
+            match fixed {
+                Some(name) => DeviceKey::Named(name.to_string()),
+                None => config.identity.key(d),
             }
-            let minimal_path = format!("{}.{}", partial_minimal_path, multi_controller);
-            let mut minimal_path_bytes = minimal_path.as_bytes().to_vec();
-
-            let common_name =
-                words.object_name(&mut minimal_path_bytes, config.path_common_name_max_length);
-
-            let js_path = std::fs::read_link(&full_path)
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-
-            let mut eventpath = "/dev/input/event".to_string();
-            eventpath.push_str(
-                gimme_event
-                    .captures(&js_path)
-                    .unwrap()
-                    .get(1)
-                    .unwrap()
-                    .as_str(),
+        })
+        .collect();
+
+    for (device, key) in devices.iter().zip(keys.iter()) {
+        let minimal_path = device.minimal_path();
+
+        // Cheap pads often share one serial, so a clash falls back to the port path
+        let key = if keys.iter().filter(|k| *k == key).count() > 1 {
+            eprintln!(
+                "{} shares its identity with another controller, naming it by port",
+                minimal_path
             );
+            DeviceKey::Hashed(minimal_path.clone())
+        } else {
+            key.clone()
+        };
+
+        let common_name = match key {
+            DeviceKey::Hashed(k) => {
+                let mut key_bytes = k.as_bytes().to_vec();
+                words.object_name(&mut key_bytes, config.path_common_name_max_length)
+            }
+            DeviceKey::Named(name) => name,
+        };
+
+        joy_paths.push(NamedPath {
+            full_path: device.full_path(),
+            minimal_path,
+            root_event_path: device.devnode.clone(),
+            common_name,
+            vendor_id: device.vendor_id(),
+            product_id: device.product_id(),
+            serial: device.serial().map(|s| s.to_string()),
+        });
+    }
+
+    joy_paths
+}
+
+pub fn repath_joys(
+    words: &Wordhash,
+    config: &crate::Config,
+) -> Result<Vec<NamedPath>, RepathError> {
+    let candidates = match &config.device_root {
+        Some(root) => root_candidates(Path::new(root))?,
+        None => udev_candidates()?,
+    };
+    Ok(name_devices(words, config, joysticks(candidates)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            joy_paths.push(NamedPath {
-                full_path: full_path.to_owned(),
-                minimal_path,
-                root_event_path: eventpath,
-                common_name: common_name.to_owned(),
-            });
+    fn device(id_path: &str) -> InputDevice {
+        InputDevice::fake(
+            "/dev/input/event7",
+            &[("ID_PATH", id_path), ("ID_VENDOR_ID", "0079")],
+        )
+    }
+
+    #[test]
+    fn minimal_paths() {
+        let pci = device("pci-0000:00:14.0-usb-0:1:1.0");
+        assert_eq!(pci.minimal_path(), "1:1.0");
+        assert_eq!(
+            pci.full_path(),
+            "/dev/input/by-path/pci-0000:00:14.0-usb-0:1:1.0-event-joystick"
+        );
+        assert_eq!(pci.vendor_id(), Some(0x0079));
+
+        let pi = device("platform-fd500000.pcie-pci-0000:01:00.0-usb-0:1.3:1.2");
+        assert_eq!(pi.minimal_path(), "1.3:1.2");
+        assert_eq!(pi.interface(), 2);
+
+        let bluetooth = InputDevice::fake("/dev/input/event7", &[("ID_VENDOR_ID", "0079")]);
+        assert_eq!(bluetooth.minimal_path(), "/dev/input/event7");
+        assert_eq!(bluetooth.interface(), 0);
+    }
+
+    fn candidate(devnode: Option<&str>, joystick: bool) -> Candidate {
+        let mut properties = HashMap::new();
+        if joystick {
+            properties.insert("ID_INPUT_JOYSTICK".to_string(), "1".to_string());
+        }
+        Candidate {
+            devnode: devnode.map(|d| d.to_string()),
+            syspath: String::new(),
+            properties,
         }
     }
 
-    Ok(joy_paths)
+    #[test]
+    fn joystick_event_nodes() {
+        // What the udev joystick match hands back for one pad, plus a keyboard
+        let devices = joysticks(vec![
+            candidate(Some("/dev/input/event9"), true),
+            candidate(None, true),
+            candidate(Some("/dev/input/js0"), true),
+            candidate(Some("/dev/input/event3"), false),
+            candidate(Some("/dev/input/event4"), true),
+        ]);
+        let devnodes: Vec<&str> = devices.iter().map(|d| d.devnode.as_str()).collect();
+        assert_eq!(devnodes, ["/dev/input/event4", "/dev/input/event9"]);
+    }
+
+    fn config(multi_port: u32, extra: &str) -> crate::Config {
+        let json = format!(
+            r#"{{
+                "team_allocation": [],
+                "path_hash_salt": 0,
+                "team_hash_salt": 0,
+                "path_common_name_max_length": 12,
+                "hat_only_players": [],
+                "number_of_multi_port_controllers_to_use": {}
+                {}
+            }}"#,
+            multi_port, extra
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn scan(case: &str, config: &crate::Config) -> Vec<InputDevice> {
+        let root = format!("tests/fixtures/joypaths/{}", case);
+        let candidates = root_candidates(Path::new(&root)).unwrap();
+        usable_devices(config, joysticks(candidates))
+    }
+
+    fn minimal_paths_of(devices: &[InputDevice]) -> Vec<String> {
+        devices.iter().map(|d| d.minimal_path()).collect()
+    }
+
+    #[test]
+    fn fixture_single_pad() {
+        let devices = scan("single", &config(4, ""));
+        assert_eq!(minimal_paths_of(&devices), ["2:1.0"]);
+        let pad = &devices[0];
+        assert_eq!(pad.vendor_id(), Some(0x0079));
+        assert_eq!(pad.product_id(), Some(0x0011));
+        assert_eq!(pad.serial(), Some("0001"));
+        assert_eq!(pad.name(), "DragonRise Inc.   Generic   USB  Joystick");
+        assert_eq!(pad.uniq(), None);
+        assert!(pad.devnode.ends_with("single/dev/input/event5"));
+    }
+
+    #[test]
+    fn fixture_multi_port_adapter() {
+        let all = scan("adapter", &config(4, ""));
+        assert_eq!(minimal_paths_of(&all), ["1:1.0", "1:1.1", "1:1.2", "1:1.3"]);
+        assert_eq!(
+            all.iter().map(|d| d.interface()).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+
+        let capped = scan("adapter", &config(2, ""));
+        assert_eq!(minimal_paths_of(&capped), ["1:1.0", "1:1.1"]);
+
+        let ports = scan(
+            "adapter",
+            &config(
+                4,
+                r#", "adapters": [{"vendor_id": "0079", "ports": [0, 3]}]"#,
+            ),
+        );
+        assert_eq!(minimal_paths_of(&ports), ["1:1.0", "1:1.3"]);
+    }
+
+    #[test]
+    fn fixture_hubs() {
+        let devices = scan("hub", &config(4, ""));
+        assert_eq!(minimal_paths_of(&devices), ["1.2:1.0", "1.4.3:1.0"]);
+        assert_eq!(devices[1].usb_port(), Some("1.4.3:1"));
+    }
+
+    #[test]
+    fn fixture_odd_paths() {
+        let devices = scan("odd_paths", &config(4, ""));
+        assert_eq!(devices.len(), 2);
+        // Bluetooth has no port path, so the event node stands in for it
+        let bluetooth = &devices[0];
+        assert!(bluetooth
+            .minimal_path()
+            .ends_with("odd_paths/dev/input/event7"));
+        assert_eq!(bluetooth.uniq().as_deref(), Some("98:b6:e9:01:02:03"));
+        assert_eq!(devices[1].minimal_path(), "1.1:1.0");
+
+        let with_virtual = scan(
+            "odd_paths",
+            &config(4, r#", "device_filter": {"allow_virtual": true}"#),
+        );
+        assert_eq!(with_virtual.len(), 3);
+        assert!(with_virtual
+            .iter()
+            .any(|d| d.is_virtual() && d.name() == "Buster0"));
+    }
+
+    #[test]
+    fn fixture_missing_event_links() {
+        let devices = scan("missing_links", &config(4, ""));
+        assert_eq!(minimal_paths_of(&devices), ["4:1.0"]);
+        assert!(root_candidates(Path::new("tests/fixtures/joypaths/nowhere")).is_err());
+    }
 }
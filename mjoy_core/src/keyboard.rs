@@ -0,0 +1,256 @@
+use crate::layout::{Layout, OutAxis};
+use crate::outjoy::Frame;
+use crate::output::Output;
+use crate::uinput::{self, Raw, UinputError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Linux key codes by the names a keymap can use
+fn key_code(name: &str) -> Option<u16> {
+    const LETTERS: &str = "qwertyuiopasdfghjklzxcvbnm";
+    const LETTER_CODES: [u16; 26] = [
+        16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 30, 31, 32, 33, 34, 35, 36, 37, 38, 44, 45, 46, 47,
+        48, 49, 50,
+    ];
+    let name = name.to_lowercase();
+    if name.len() == 1 {
+        let c = name.chars().next()?;
+        if let Some(i) = LETTERS.find(c) {
+            return Some(LETTER_CODES[i]);
+        }
+        if let Some(d) = c.to_digit(10) {
+            // KEY_1 is 2 and KEY_0 comes after KEY_9
+            return Some(if d == 0 { 11 } else { d as u16 + 1 });
+        }
+    }
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<u16>().ok()) {
+        return match n {
+            1..=10 => Some(58 + n),
+            11 | 12 => Some(76 + n),
+            _ => None,
+        };
+    }
+    let code = match name.as_str() {
+        "escape" | "esc" => 1,
+        "minus" => 12,
+        "equal" => 13,
+        "backspace" => 14,
+        "tab" => 15,
+        "left_brace" => 26,
+        "right_brace" => 27,
+        "enter" => 28,
+        "left_ctrl" => 29,
+        "semicolon" => 39,
+        "apostrophe" => 40,
+        "grave" => 41,
+        "left_shift" => 42,
+        "backslash" => 43,
+        "comma" => 51,
+        "dot" => 52,
+        "slash" => 53,
+        "right_shift" => 54,
+        "left_alt" => 56,
+        "space" => 57,
+        "right_ctrl" => 97,
+        "right_alt" => 100,
+        "home" => 102,
+        "up" => 103,
+        "page_up" => 104,
+        "left" => 105,
+        "right" => 106,
+        "end" => 107,
+        "down" => 108,
+        "page_down" => 109,
+        "insert" => 110,
+        "delete" => 111,
+        _ => return None,
+    };
+    Some(code)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AxisKeys {
+    pub negative: String,
+    pub positive: String,
+}
+
+fn default_axis_threshold() -> f32 {
+    0.5
+}
+
+// Keys are named like "x", "7", "f3", "enter" or "left_shift"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keymap {
+    // Layout button name -> key
+    #[serde(default)]
+    pub buttons: HashMap<String, String>,
+    // Layout axis name -> a key each way. Unmapped X and Y axes use the arrow keys.
+    #[serde(default)]
+    pub axes: HashMap<String, AxisKeys>,
+    // How far an axis has to be pushed to hold its key
+    #[serde(default = "default_axis_threshold")]
+    pub axis_threshold: f32,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            buttons: HashMap::new(),
+            axes: HashMap::new(),
+            axis_threshold: default_axis_threshold(),
+        }
+    }
+}
+
+fn resolve(key: &str) -> u16 {
+    key_code(key).unwrap_or_else(|| panic!("Unknown key \"{}\" in keymap", key))
+}
+
+// Key codes per layout control, so a frame can be turned into key states
+struct ResolvedKeymap {
+    buttons: Vec<Option<u16>>,
+    // (negative, positive)
+    axes: Vec<Option<(u16, u16)>>,
+    threshold: f32,
+}
+
+impl ResolvedKeymap {
+    fn new(keymap: &Keymap, layout: &Layout) -> Self {
+        for name in keymap.buttons.keys() {
+            if !layout.buttons.iter().any(|b| &b.name == name) {
+                panic!("Keymap button {} is not in layout {}", name, layout.name);
+            }
+        }
+        for name in keymap.axes.keys() {
+            if !layout.axes.iter().any(|a| &a.name == name) {
+                panic!("Keymap axis {} is not in layout {}", name, layout.name);
+            }
+        }
+
+        let buttons = layout
+            .buttons
+            .iter()
+            .map(|b| keymap.buttons.get(&b.name).map(|k| resolve(k)))
+            .collect();
+        // Frames are positive up, so Y's negative key is down
+        let axes = layout
+            .axes
+            .iter()
+            .map(|a| match (keymap.axes.get(&a.name), a.output) {
+                (Some(keys), _) => Some((resolve(&keys.negative), resolve(&keys.positive))),
+                (None, OutAxis::X) => Some((resolve("left"), resolve("right"))),
+                (None, OutAxis::Y) => Some((resolve("down"), resolve("up"))),
+                (None, _) => None,
+            })
+            .collect();
+        ResolvedKeymap {
+            buttons,
+            axes,
+            threshold: keymap.axis_threshold,
+        }
+    }
+
+    fn codes(&self) -> Vec<u16> {
+        let mut codes: Vec<u16> = self.buttons.iter().flatten().copied().collect();
+        for (negative, positive) in self.axes.iter().flatten() {
+            codes.push(*negative);
+            codes.push(*positive);
+        }
+        codes.sort_unstable();
+        codes.dedup();
+        codes
+    }
+
+    // A key bound to several controls is held while any of them is
+    fn pressed(&self, frame: &Frame) -> Vec<u16> {
+        let mut pressed = Vec::new();
+        for (code, down) in self.buttons.iter().zip(frame.buttons.iter()) {
+            if let (Some(code), true) = (code, down) {
+                pressed.push(*code);
+            }
+        }
+        for (keys, value) in self.axes.iter().zip(frame.axes.iter()) {
+            match keys {
+                Some((negative, _)) if *value <= -self.threshold => pressed.push(*negative),
+                Some((_, positive)) if *value >= self.threshold => pressed.push(*positive),
+                _ => {}
+            }
+        }
+        pressed
+    }
+}
+
+// A team's virtual keyboard
+pub struct Keyboard {
+    raw: Raw,
+    keymap: ResolvedKeymap,
+    codes: Vec<u16>,
+}
+
+impl Keyboard {
+    pub fn new(
+        keymap: &Keymap,
+        layout: &Layout,
+        device: &uinput::DeviceConfig,
+        name: &str,
+    ) -> Result<Self, UinputError> {
+        let keymap = ResolvedKeymap::new(keymap, layout);
+        let codes = keymap.codes();
+        Ok(Keyboard {
+            raw: Raw::new(name, device.ids(), &codes, &[], false)?,
+            keymap,
+            codes,
+        })
+    }
+}
+
+impl Output for Keyboard {
+    fn write(&mut self, _layout: &Layout, frame: &Frame) {
+        let pressed = self.keymap.pressed(frame);
+        for code in self.codes.iter() {
+            self.raw.key(*code, pressed.contains(code));
+        }
+    }
+
+    fn synchronise(&mut self) {
+        self.raw.sync();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keymap_to_keys() {
+        let layout = Layout::load("../resources/layouts/snes.json");
+        let json = r#"{
+            "buttons": {"A": "x", "B": "z", "Start": "enter", "Select": "Enter"},
+            "axis_threshold": 0.3
+        }"#;
+        let keymap: Keymap = serde_json::from_str(json).unwrap();
+        let keymap = ResolvedKeymap::new(&keymap, &layout);
+        // x, z, enter and the four arrows
+        assert_eq!(keymap.codes(), [28, 44, 45, 103, 105, 106, 108]);
+
+        let mut frame = Frame {
+            axes: vec![-0.4, 0.1],
+            buttons: vec![false; layout.buttons.len()],
+        };
+        frame.buttons[0] = true;
+        frame.buttons[7] = true;
+        let pressed = keymap.pressed(&frame);
+        assert_eq!(pressed, [45, 28, 105]);
+
+        // Pushed up is up
+        frame.axes = vec![0.0, 0.6];
+        frame.buttons = vec![false; layout.buttons.len()];
+        assert_eq!(keymap.pressed(&frame), [103]);
+        frame.axes = vec![0.0, -0.6];
+        assert_eq!(keymap.pressed(&frame), [108]);
+
+        assert_eq!(key_code("f11"), Some(87));
+        assert_eq!(key_code("0"), Some(11));
+        assert_eq!(key_code("hyper"), None);
+    }
+}
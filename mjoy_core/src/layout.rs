@@ -0,0 +1,153 @@
+use crate::hysteresis::Hysteresis;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OutButton {
+    LeftNorth,
+    LeftSouth,
+    LeftEast,
+    LeftWest,
+    RightNorth,
+    RightSouth,
+    RightEast,
+    RightWest,
+    LeftSpecial,
+    RightSpecial,
+    L1,
+    L2,
+    R1,
+    R2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OutAxis {
+    X,
+    Y,
+    RX,
+    RY,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ButtonControl {
+    pub name: String,
+    pub input: gilrs::Button,
+    pub output: OutButton,
+    pub glyph: String,
+    // Overrides the layout wide hysteresis for this button
+    #[serde(default)]
+    pub hysteresis: Option<Hysteresis>,
+    // Button name for the Dolphin pipe output, like "Z"
+    #[serde(default)]
+    pub dolphin: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DpadInput {
+    pub button: gilrs::Button,
+    pub scalar: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StickInput {
+    pub axis: gilrs::Axis,
+    pub scalar: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AxisGlyphs {
+    pub negative: String,
+    pub positive: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AxisControl {
+    pub name: String,
+    #[serde(default)]
+    pub dpad: Option<DpadInput>,
+    #[serde(default)]
+    pub stick: Option<StickInput>,
+    pub output: OutAxis,
+    #[serde(default)]
+    pub glyphs: Option<AxisGlyphs>,
+    // Stick half or trigger for the Dolphin pipe output, like "C x" or "L"
+    #[serde(default)]
+    pub dolphin: Option<String>,
+}
+
+impl AxisControl {
+    pub fn glyph(&self, value: f32) -> Option<&str> {
+        let glyphs = self.glyphs.as_ref()?;
+        match value {
+            f if f > 0.1 => Some(&glyphs.positive),
+            f if f < -0.1 => Some(&glyphs.negative),
+            _ => None,
+        }
+    }
+
+    pub fn all_glyphs(&self) -> Vec<String> {
+        match &self.glyphs {
+            Some(glyphs) => vec![glyphs.negative.clone(), glyphs.positive.clone()],
+            None => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Layout {
+    pub name: String,
+    pub buttons: Vec<ButtonControl>,
+    pub axes: Vec<AxisControl>,
+    #[serde(default)]
+    pub hysteresis: Hysteresis,
+}
+
+impl Layout {
+    pub fn load(path: &str) -> Layout {
+        let text = std::fs::read_to_string(path).expect("Failed to read layout file");
+        serde_json::from_str::<Layout>(&text).expect("Failed to parse layout file")
+    }
+
+    pub fn hysteresis<'a>(&'a self, button: &'a ButtonControl) -> &'a Hysteresis {
+        button.hysteresis.as_ref().unwrap_or(&self.hysteresis)
+    }
+
+    // Order in which controls are shown in the GUI feedback rows
+    pub fn feedback_glyphs(&self) -> Vec<String> {
+        let mut glyphs = Vec::new();
+        for axis in self.axes.iter() {
+            // Left before right, but up before down, as the feedback row always read
+            let mut axis_glyphs = axis.all_glyphs();
+            if matches!(axis.output, OutAxis::Y | OutAxis::RY) {
+                axis_glyphs.reverse();
+            }
+            glyphs.extend(axis_glyphs);
+        }
+        for button in self.buttons.iter() {
+            glyphs.push(button.glyph.clone());
+        }
+        glyphs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_layouts_parse() {
+        for name in ["snes", "n64", "gamecube", "xbox"] {
+            let layout = Layout::load(&format!("../resources/layouts/{}.json", name));
+            assert!(!layout.buttons.is_empty());
+            assert!(!layout.axes.is_empty());
+        }
+    }
+
+    #[test]
+    fn snes_feedback_glyphs() {
+        let layout = Layout::load("../resources/layouts/snes.json");
+        assert_eq!(
+            layout.feedback_glyphs(),
+            ["<", ">", "^", "v", "A", "B", "X", "Y", "L", "R", "t", "e"]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use crate::injoy::{self, AxisSources};
+use crate::input::InputSource;
+use crate::layout::{AxisControl, ButtonControl, Layout, OutAxis};
+use mjoy_gui::gui::lobby_info;
+
+// The layout's controls the lobby listens to
+struct Controls<'a> {
+    confirm: Option<&'a ButtonControl>,
+    cancel: Option<&'a ButtonControl>,
+    x: Option<&'a AxisControl>,
+    sources: &'a AxisSources,
+}
+
+impl<'a> Controls<'a> {
+    fn new(layout: &'a Layout, sources: &'a AxisSources) -> Self {
+        let button = |name: &str| layout.buttons.iter().find(|b| b.name == name);
+        Controls {
+            confirm: button("A"),
+            cancel: button("B"),
+            x: layout.axes.iter().find(|a| a.output == OutAxis::X),
+            sources,
+        }
+    }
+}
+
+// Buttons a player uses in the lobby, as last read
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Held {
+    left: bool,
+    right: bool,
+    confirm: bool,
+    cancel: bool,
+}
+
+impl Held {
+    // Read through the layout, the same way team output is
+    fn read(input: &dyn InputSource, player: &str, controls: &Controls) -> Self {
+        let button = |b: Option<&ButtonControl>| match b {
+            Some(b) => injoy::button_value(input, player, b) > 0.5,
+            None => false,
+        };
+        let x = match controls.x {
+            Some(x) => injoy::axis_value(input, player, x, controls.sources),
+            None => 0f32,
+        };
+        Held {
+            left: x < -0.5,
+            right: x > 0.5,
+            confirm: button(controls.confirm),
+            cancel: button(controls.cancel),
+        }
+    }
+}
+
+struct Pick {
+    player: String,
+    team: usize,
+    confirmed: bool,
+    held: Held,
+}
+
+// Players move between teams with left and right, confirm with A and back out
+// with B. A team takes no more confirmed players than its allocation.
+pub struct Draft<'a> {
+    controls: Controls<'a>,
+    capacities: Vec<u32>,
+    picks: Vec<Pick>,
+}
+
+impl<'a> Draft<'a> {
+    pub fn new(
+        players: &[String],
+        capacities: &[u32],
+        layout: &'a Layout,
+        sources: &'a AxisSources,
+    ) -> Self {
+        // Start spread over the teams so nobody has far to move
+        let picks = players
+            .iter()
+            .enumerate()
+            .map(|(i, player)| Pick {
+                player: player.clone(),
+                team: i % capacities.len().max(1),
+                confirmed: false,
+                held: Held::default(),
+            })
+            .collect();
+        Draft {
+            controls: Controls::new(layout, sources),
+            capacities: capacities.to_vec(),
+            picks,
+        }
+    }
+
+    fn confirmed_on(&self, team: usize) -> u32 {
+        self.picks
+            .iter()
+            .filter(|p| p.confirmed && p.team == team)
+            .count() as u32
+    }
+
+    fn has_room(&self, team: usize) -> bool {
+        self.confirmed_on(team) < self.capacities[team]
+    }
+
+    // The next team over with room, or where the player already is
+    fn step(&self, from: usize, forward: bool) -> usize {
+        let n = self.capacities.len();
+        for i in 1..n {
+            let team = if forward {
+                (from + i) % n
+            } else {
+                (from + n - i) % n
+            };
+            if self.has_room(team) {
+                return team;
+            }
+        }
+        from
+    }
+
+    // Acts on presses since the last update
+    pub fn update(&mut self, input: &dyn InputSource) {
+        for i in 0..self.picks.len() {
+            let held = Held::read(input, &self.picks[i].player, &self.controls);
+            let was = self.picks[i].held;
+            self.picks[i].held = held;
+            let pick = &self.picks[i];
+
+            if pick.confirmed {
+                if held.cancel && !was.cancel {
+                    self.picks[i].confirmed = false;
+                }
+                continue;
+            }
+            if held.left && !was.left {
+                self.picks[i].team = self.step(pick.team, false);
+            } else if held.right && !was.right {
+                self.picks[i].team = self.step(pick.team, true);
+            } else if held.confirm && !was.confirm && self.has_room(pick.team) {
+                self.picks[i].confirmed = true;
+            }
+        }
+    }
+
+    pub fn done(&self) -> bool {
+        self.picks.iter().all(|p| p.confirmed)
+    }
+
+    // Players per team, in the order they joined the draft
+    pub fn teams(&self) -> Vec<Vec<String>> {
+        (0..self.capacities.len())
+            .map(|team| {
+                self.picks
+                    .iter()
+                    .filter(|p| p.team == team)
+                    .map(|p| p.player.clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn info(&self) -> lobby_info::LobbyInfo {
+        let teams = self
+            .capacities
+            .iter()
+            .enumerate()
+            .map(|(team, capacity)| lobby_info::LobbyTeam {
+                label: format!("Team {}", team + 1),
+                capacity: *capacity,
+                players: self
+                    .picks
+                    .iter()
+                    .filter(|p| p.team == team)
+                    .map(|p| lobby_info::LobbyPlayer {
+                        player_name: p.player.clone(),
+                        confirmed: p.confirmed,
+                    })
+                    .collect(),
+            })
+            .collect();
+        lobby_info::LobbyInfo { teams }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{ScriptedInput, Timeline};
+    use std::time::{Duration, Instant};
+
+    fn at(input: &mut ScriptedInput, draft: &mut Draft, start: Instant, ms: u64) {
+        input.poll(start + Duration::from_millis(ms));
+        draft.update(input);
+    }
+
+    // SNES pads put A on East and B on South, and report the D-pad's X through
+    // DPadRight: 0 is left, 0.5 centred and 1 right
+    #[test]
+    fn draft_respects_allocation() {
+        let json = r#"{
+            "players": [{"name": "ann"}, {"name": "bob"}, {"name": "cat"}],
+            "events": [
+                {"at_ms": 0, "player": "ann", "button": "DPadRight", "value": 0.5},
+                {"at_ms": 0, "player": "ann", "button": "East", "value": 1.0},
+                {"at_ms": 0, "player": "bob", "button": "DPadRight", "value": 0.0},
+                {"at_ms": 0, "player": "cat", "button": "DPadRight", "value": 0.5},
+                {"at_ms": 0, "player": "cat", "button": "South", "value": 1.0},
+                {"at_ms": 100, "player": "bob", "button": "DPadRight", "value": 0.5},
+                {"at_ms": 100, "player": "bob", "button": "East", "value": 1.0},
+                {"at_ms": 100, "player": "cat", "button": "South", "value": 0.0},
+                {"at_ms": 100, "player": "cat", "button": "DPadRight", "value": 0.0},
+                {"at_ms": 200, "player": "cat", "button": "East", "value": 1.0},
+                {"at_ms": 300, "player": "cat", "button": "DPadRight", "value": 0.5},
+                {"at_ms": 300, "player": "bob", "button": "East", "value": 0.0},
+                {"at_ms": 300, "player": "bob", "button": "South", "value": 1.0},
+                {"at_ms": 400, "player": "bob", "button": "South", "value": 0.0},
+                {"at_ms": 400, "player": "bob", "button": "DPadRight", "value": 1.0},
+                {"at_ms": 500, "player": "bob", "button": "East", "value": 1.0}
+            ]
+        }"#;
+        let timeline: Timeline = serde_json::from_str(json).unwrap();
+        let mut input = ScriptedInput::new(timeline);
+        let layout = Layout::load("../resources/layouts/snes.json");
+        let sources = AxisSources::default();
+        let players = vec!["ann".to_string(), "bob".to_string(), "cat".to_string()];
+        let mut draft = Draft::new(&players, &[2, 1], &layout, &sources);
+
+        let start = Instant::now();
+        let confirmed = |draft: &Draft, player: &str| {
+            draft
+                .info()
+                .teams
+                .iter()
+                .flat_map(|t| t.players.iter())
+                .any(|p| p.player_name == player && p.confirmed)
+        };
+
+        // Ann confirms the starting team with A, bob moves over to team 1 and
+        // cat's B does nothing
+        at(&mut input, &mut draft, start, 0);
+        assert!(confirmed(&draft, "ann"));
+        assert!(!confirmed(&draft, "cat"));
+        assert_eq!(draft.teams(), [vec!["ann", "bob", "cat"], vec![]]);
+
+        // Cat, who also started on team 1, moves off to team 2
+        at(&mut input, &mut draft, start, 100);
+        assert_eq!(draft.teams(), [vec!["ann", "bob"], vec!["cat"]]);
+
+        // Cat confirms, which fills every team
+        at(&mut input, &mut draft, start, 200);
+        assert!(draft.done());
+
+        // Bob backs out with B, but team 2 is full so bob can't move
+        at(&mut input, &mut draft, start, 300);
+        at(&mut input, &mut draft, start, 400);
+        assert!(!draft.done());
+        assert_eq!(draft.teams(), [vec!["ann", "bob"], vec!["cat"]]);
+        at(&mut input, &mut draft, start, 500);
+        assert!(draft.done());
+
+        let info = draft.info();
+        assert_eq!(info.teams[1].label, "Team 2");
+        assert_eq!(info.teams[0].players.len(), 2);
+    }
+}
@@ -1,15 +1,51 @@
+mod adapters;
+mod aggregate;
+mod connection;
+mod consensus;
+mod curve;
+mod delay;
+mod dolphin;
+mod draw;
+mod filter;
+mod hex_id;
+mod hysteresis;
+mod identity;
 mod injoy;
+mod input;
 mod joypaths;
+mod keyboard;
+mod layout;
+mod lobby;
+mod modifiers;
 mod outjoy;
+mod output;
+mod rumble;
+mod split;
+mod threshold;
+mod tick;
+mod turns;
+mod uinput;
 
 use clap::Parser;
-use rand;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Parser)]
 struct Cli {
     #[clap(short, long, default_value = "config.json")]
     config: String,
+    // Replay a JSON timeline of controller input instead of reading real gamepads
+    #[clap(long)]
+    script: Option<String>,
+    // Without a teamlock, let players pick their teams in the GUI rather than
+    // going by port order
+    #[clap(long)]
+    lobby: bool,
+    // Override the config's team draw strategy and seed
+    #[clap(long, value_enum)]
+    draw: Option<draw::Strategy>,
+    #[clap(long)]
+    seed: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,6 +56,54 @@ pub struct Config {
     path_common_name_max_length: u32,
     hat_only_players: Vec<String>,
     number_of_multi_port_controllers_to_use: u32,
+    #[serde(default)]
+    team_aggregation: Vec<aggregate::TeamAggregation>,
+    #[serde(default)]
+    axis_sources: injoy::AxisSources,
+    #[serde(default)]
+    axis_response: curve::AxisResponses,
+    #[serde(default = "default_layout")]
+    layout: String,
+    #[serde(default)]
+    team_modes: Vec<turns::TeamMode>,
+    #[serde(default)]
+    control_splits: Vec<Vec<Vec<String>>>,
+    #[serde(default)]
+    threshold: threshold::ThresholdConfig,
+    #[serde(default)]
+    team_delays: Vec<delay::DelayConfig>,
+    #[serde(default)]
+    team_modifiers: Vec<Vec<modifiers::Modifier>>,
+    #[serde(default)]
+    tick: tick::TickConfig,
+    #[serde(default)]
+    identity: identity::Identity,
+    #[serde(default)]
+    adapters: Vec<adapters::Adapter>,
+    #[serde(default)]
+    connection: connection::ConnectionConfig,
+    #[serde(default)]
+    device_filter: filter::FilterConfig,
+    // Discover devices from a sysfs and udev database tree under this directory
+    // rather than asking udev
+    #[serde(default)]
+    device_root: Option<String>,
+    #[serde(default)]
+    output: output::OutputConfig,
+    // Overrides `output` per team
+    #[serde(default)]
+    team_outputs: Vec<output::OutputConfig>,
+    #[serde(default)]
+    team_devices: Vec<uinput::DeviceConfig>,
+    #[serde(default)]
+    team_rumble: Vec<rumble::RumbleConfig>,
+    // How players are split into teams when there is no teamlock
+    #[serde(default)]
+    team_draw: draw::DrawConfig,
+}
+
+fn default_layout() -> String {
+    "./resources/layouts/snes.json".to_string()
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -27,11 +111,42 @@ pub struct Team {
     name: String,
     players: Vec<String>,
     out_index: u32,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    controls: split::ControlOwnership,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TeamLock {
     teams: Vec<Team>,
+    // Reproduces a random draw when put back in the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+// Names each team after its players and splits its controls
+fn team_lock(teams: Vec<Vec<String>>, seed: Option<u64>, config: &Config) -> TeamLock {
+    let mut frozen = TeamLock {
+        teams: Vec::new(),
+        seed,
+    };
+    for (team_index, team) in teams.into_iter().enumerate() {
+        let mut concat = String::new();
+        for player in team.iter() {
+            concat.push_str(player);
+            concat.push('.');
+        }
+
+        let team_name = mjoy_gui::diskteamhash::team_hash(config.team_hash_salt, concat.as_bytes());
+
+        let controls = split::assign(&team, config.control_splits.get(team_index));
+        frozen.teams.push(Team {
+            name: team_name,
+            players: team,
+            out_index: team_index as u32,
+            controls,
+        });
+    }
+    frozen
 }
 
 fn main() {
@@ -39,23 +154,43 @@ fn main() {
     let config = serde_json::from_str::<Config>(&std::fs::read_to_string(&args.config).unwrap())
         .expect("Failed to parse config file");
     dbg!(&config);
+    let layout = layout::Layout::load(&config.layout);
+    for modifiers in config.team_modifiers.iter() {
+        if let Err(e) = modifiers::Modifiers::new(modifiers, &layout) {
+            eprintln!("Invalid team_modifiers in {}: {}", args.config, e);
+            std::process::exit(1);
+        }
+    }
     let words = mjoy_gui::wordhash::Wordhash::new(config.path_hash_salt, config.team_hash_salt);
 
+    let mut input: Box<dyn input::InputSource> = match &args.script {
+        Some(script) => Box::new(input::ScriptedInput::load(script)),
+        None => Box::new(input::GilrsInput::new(&words, &config)),
+    };
+
     // Read configuration file .json file
-    let joy_paths = joypaths::repath_joys(&words, &config);
-    let mpl: joypaths::MinimalPathLookup = joy_paths.unwrap().into();
+    let mpl: joypaths::MinimalPathLookup = input.named_paths().into();
     let mut minimal_paths: Vec<&String> = mpl.0.keys().collect();
     minimal_paths.sort();
     minimal_paths.reverse();
 
     for path in minimal_paths.iter() {
         let joy = &mpl.0[*path];
-        println!("{: <15} -> {: <20}", joy.common_name, path);
+        println!(
+            "{: <15} -> {: <20} {}",
+            joy.common_name,
+            path,
+            joy.describe()
+        );
     }
 
+    use mjoy_gui::gui::Ui;
+    let mut ui = Ui::without_teams(mjoy_gui::gui::WidthHeight::new(1920, 1080));
+    let mut ticker = tick::Ticker::new(&config.tick);
+
     let frozen_path = "teamlock.json";
     // Check for a teamlock.json file
-    let frozen = if std::path::Path::new(&frozen_path).exists() {
+    let mut frozen = if std::path::Path::new(&frozen_path).exists() {
         // If it exists, read it and return it
         let frozen =
             serde_json::from_str::<TeamLock>(&std::fs::read_to_string(&frozen_path).unwrap())
@@ -73,35 +208,53 @@ fn main() {
             std::process::exit(1);
         }
 
-        let mut frozen: TeamLock = TeamLock { teams: Vec::new() };
-        let mut minimal_path_index = 0;
-        for team_index in 0..config.team_allocation.len() {
-            let mut team = Vec::new();
-            for _ in 0..config.team_allocation[team_index] {
-                let path = &minimal_paths[minimal_path_index];
-                let joy = &mpl.0[*path];
-                team.push(joy.common_name.clone());
-                minimal_path_index += 1;
+        let players: Vec<String> = minimal_paths
+            .iter()
+            .map(|path| mpl.0[*path].common_name.clone())
+            .collect();
+        let (teams, seed) = if args.lobby {
+            let mut draft = lobby::Draft::new(
+                &players,
+                &config.team_allocation,
+                &layout,
+                &config.axis_sources,
+            );
+            while !draft.done() {
+                let now = ticker.wait();
+                input.poll(now);
+                draft.update(input.as_ref());
+                if ticker.render_due(now) {
+                    ui.render_lobby(&draft.info());
+                }
             }
-
-            let mut concat = String::new();
-            for player in team.iter() {
-                concat.push_str(player);
-                concat.push_str(".");
+            (draft.teams(), None)
+        } else {
+            let mut draw_config = config.team_draw.clone();
+            if let Some(strategy) = args.draw {
+                draw_config.strategy = strategy;
             }
-
-            let team_name =
-                mjoy_gui::diskteamhash::team_hash(config.team_hash_salt, concat.as_bytes());
-
-            let team = Team {
-                name: team_name,
-                players: team,
-                out_index: team_index as u32,
-            };
-            frozen.teams.push(team);
-        }
-        frozen
+            if args.seed.is_some() {
+                draw_config.seed = args.seed;
+            }
+            if draw_config.seed.is_some() && draw_config.strategy == draw::Strategy::PortOrder {
+                eprintln!("The team draw seed does nothing with port_order");
+            }
+            let draw = draw::draw(&draw_config, &players, &config.team_allocation);
+            if let Some(seed) = draw.seed {
+                println!("Team draw seed: {}", seed);
+            }
+            (draw.teams, draw.seed)
+        };
+        team_lock(teams, seed, &config)
     };
+    for team in frozen.teams.iter_mut() {
+        split::apply_hat_only(
+            &mut team.controls,
+            &team.players,
+            &config.hat_only_players,
+            &layout,
+        );
+    }
     dbg!(&frozen);
 
     // Check frozen
@@ -143,28 +296,21 @@ fn main() {
     let frozen_json = serde_json::to_string_pretty(&frozen).unwrap();
     std::fs::write(frozen_path, frozen_json).unwrap();
 
-    use gilrs;
-
-    let mut gilrs = gilrs::Gilrs::new().unwrap();
-
-    // Iterate over all connected gamepads
-    for (_id, gamepad) in gilrs.gamepads() {
-        println!(
-            "{} is {:?} {}",
-            gamepad.name(),
-            gamepad.power_info(),
-            gamepad.devpath()
-        );
-    }
-    let mut joy_lookup: joypaths::EventPathLookup =
-        joypaths::repath_joys(&words, &config).unwrap().into();
+    let all_players: Vec<String> = frozen
+        .teams
+        .iter()
+        .flat_map(|t| t.players.iter().cloned())
+        .collect();
+    let mut connections = connection::Connections::new(&config.connection, all_players.clone());
+    let aliases = connections.reattach(&input.players());
+    input.rename(&aliases);
 
     let feedback = {
         let mut fb = Vec::new();
 
-        for thing in ["<", ">", "^", "v", "A", "B", "X", "Y", "L", "R", "t", "e"].iter() {
+        for thing in layout.feedback_glyphs() {
             fb.push(mjoy_gui::gui::feedback_info::ButtonPress {
-                button: thing.to_string(),
+                button: thing,
                 state: mjoy_gui::gui::feedback_info::PressState::Unpressed,
             });
         }
@@ -179,6 +325,12 @@ fn main() {
             let fbplayer = mjoy_gui::gui::feedback_info::Player {
                 player_name: player.clone(),
                 feedback: feedback.clone(),
+                active: true,
+                connection: mjoy_gui::gui::feedback_info::Connection::Connected,
+                assigned: team
+                    .controls
+                    .get(player)
+                    .map(|controls| split::glyphs(controls, &layout)),
             };
             fbplayers.push(fbplayer);
         }
@@ -187,6 +339,13 @@ fn main() {
             team_name: &team.name,
             players: fbplayers,
             feedback: feedback.clone(),
+            threshold: None,
+            modifiers: config
+                .team_modifiers
+                .get(team.out_index as usize)
+                .map(|m| m.iter().map(modifiers::Modifier::label).collect())
+                .unwrap_or_default(),
+            modifiers_enabled: true,
         };
 
         fbteams.push(fb_team);
@@ -194,122 +353,48 @@ fn main() {
     let mut fbinfo = mjoy_gui::gui::feedback_info::FeedbackInfo { teams: fbteams };
 
     let mut gui_teams = Vec::new();
-    use mjoy_gui::gui::Ui;
 
     for team in frozen.teams.iter() {
         gui_teams.push(team.name.clone());
     }
+    ui.set_teams(&gui_teams);
 
-    let mut ui = Ui::new(
-        gui_teams.as_slice(),
-        mjoy_gui::gui::WidthHeight::new(1920, 1080),
-    );
-
-    let all_joys = outjoy::Outjoys::new(&frozen);
-    let mut thresh = 0.9f32;
-    let mut change_thresh_time = std::time::Instant::now() + std::time::Duration::from_secs(1);
-    let mut gui_render_time = std::time::Instant::now();
+    let mut all_joys = outjoy::Outjoys::new(&frozen, &layout, &config);
+    let mut thresholds = threshold::Thresholds::new(&config.threshold, frozen.teams.len());
     let mut started = false;
+    let mut rumble = Vec::new();
     loop {
-        let event = gilrs.next_event();
-
-        match &event {
-            Some(gilrs::Event {
-                event: gilrs::EventType::Connected | gilrs::EventType::Disconnected,
-                ..
-            }) => {
-                joy_lookup = joypaths::repath_joys(&words, &config).unwrap().into();
-                continue;
-            }
-            _ => {}
-        }
+        let now = ticker.wait();
 
-        if event.is_some() {
-            continue;
+        if input.poll(now) {
+            let aliases = connections.reattach(&input.players());
+            input.rename(&aliases);
         }
+        connections.update(&input.players(), now);
 
         all_joys.update(&mut outjoy::UpdateContext {
-            gilrs: &mut gilrs,
-            event_path_lookup: &joy_lookup,
+            now,
+            input: input.as_ref(),
             feedback: &mut fbinfo,
-            stick_only_names: &config.hat_only_players,
-            button_threshold: thresh,
+            axis_sources: &config.axis_sources,
+            thresholds: &thresholds,
+            connections: &connections,
+            rumble: &mut rumble,
         });
+        for (players, r) in rumble.drain(..) {
+            input.rumble(&players, &r);
+        }
+        thresholds.update(now);
 
-        if std::time::Instant::now()
-            .checked_duration_since(gui_render_time)
-            .is_some()
-        {
-            gui_render_time = std::time::Instant::now() + std::time::Duration::from_millis(50);
+        if ticker.render_due(now) {
             ui.render(&fbinfo, started);
-
-            if !started {
-                let mut should_start = true;
-                for (i, joystick) in gilrs.gamepads() {
-                    let devpath = joystick.devpath();
-                    let common_name = match &joy_lookup.0.get(devpath) {
-                        Some(name) => &name.common_name,
-                        None => {
-                            continue;
-                        }
-                    };
-                    for team in frozen.teams.iter() {
-                        if !should_start {
-                            break;
-                        }
-
-                        for player in team.players.iter() {
-                            if player == common_name {
-                                match joystick.button_data(gilrs::Button::West) {
-                                    Some(btn) => {
-                                        if !btn.is_pressed() {
-                                            should_start = false;
-                                        }
-                                    }
-                                    None => {
-                                        should_start = false;
-                                    }
-                                }
-                                match joystick.button_data(gilrs::Button::DPadRight) {
-                                    Some(btn) => {
-                                        if !(btn.value() < 0.1f32) {
-                                            should_start = false;
-                                        }
-                                    }
-                                    None => {
-                                        should_start = false;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-
-                if should_start {
-                    started = true;
-                }
+            for key in ui.function_keys() {
+                all_joys.toggle_modifiers(key as usize - 1);
             }
-        } else {
-            continue;
-        }
 
-        let now = std::time::Instant::now();
-        if now.checked_duration_since(change_thresh_time).is_some() {
-            change_thresh_time = change_thresh_time + {
-                // Random number up to 5000
-                let random_millis = rand::random::<u64>() % 5000;
-                let random_millis = random_millis + 300;
-                std::time::Duration::from_millis(random_millis)
-            };
-            thresh = {
-                let rand = rand::random::<u64>();
-                let rand = rand % 10000;
-                let rand = rand as f32;
-                let rand = rand / 10000.0;
-                let mut rand = rand * 0.61;
-                rand += 0.49;
-                rand.min(0.95f32)
-            };
+            if !started && input::start_gesture_held(input.as_ref(), &all_players) {
+                started = true;
+            }
         }
 
         //if let Some(gilrs::Event { id, event, time }) = event {
@@ -0,0 +1,258 @@
+use crate::layout::Layout;
+use crate::outjoy::Frame;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "modifier", rename_all = "snake_case")]
+pub enum Modifier {
+    InvertAxis {
+        axis: String,
+    },
+    SwapButtons {
+        a: String,
+        b: String,
+    },
+    // Each press toggles the button between held and released
+    Sticky {
+        button: String,
+    },
+    // Holding the button fires it repeatedly
+    Turbo {
+        button: String,
+        #[serde(default = "default_rate_hz", deserialize_with = "rate_hz")]
+        rate_hz: f32,
+    },
+}
+
+fn default_rate_hz() -> f32 {
+    10f32
+}
+
+// Faster than the tick can't be seen anyway, and much faster leaves no half
+// period to count in
+const MAX_RATE_HZ: f32 = 1000f32;
+
+fn rate_hz<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let rate = f32::deserialize(deserializer)?;
+    if rate > 0f32 && rate <= MAX_RATE_HZ {
+        Ok(rate)
+    } else {
+        Err(de::Error::custom(format!(
+            "rate_hz must be above 0 and at most {}, not {}",
+            MAX_RATE_HZ, rate
+        )))
+    }
+}
+
+impl Modifier {
+    pub fn label(&self) -> String {
+        match self {
+            Modifier::InvertAxis { axis } => format!("-{}", axis),
+            Modifier::SwapButtons { a, b } => format!("{}<>{}", a, b),
+            Modifier::Sticky { button } => format!("sticky {}", button),
+            Modifier::Turbo { button, .. } => format!("turbo {}", button),
+        }
+    }
+}
+
+enum Active {
+    Invert(usize),
+    Swap(usize, usize),
+    Sticky {
+        index: usize,
+        held: bool,
+        latched: bool,
+    },
+    Turbo {
+        index: usize,
+        half_period: Duration,
+        since: Option<Instant>,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ModifierError {
+    #[error("Modifier names unknown button {0}")]
+    UnknownButton(String),
+    #[error("Modifier names unknown axis {0}")]
+    UnknownAxis(String),
+}
+
+fn button_index(layout: &Layout, name: &str) -> Result<usize, ModifierError> {
+    layout
+        .buttons
+        .iter()
+        .position(|b| b.name == name)
+        .ok_or_else(|| ModifierError::UnknownButton(name.to_string()))
+}
+
+fn axis_index(layout: &Layout, name: &str) -> Result<usize, ModifierError> {
+    layout
+        .axes
+        .iter()
+        .position(|a| a.name == name)
+        .ok_or_else(|| ModifierError::UnknownAxis(name.to_string()))
+}
+
+pub struct Modifiers {
+    active: Vec<Active>,
+    enabled: bool,
+}
+
+impl Modifiers {
+    pub fn new(modifiers: &[Modifier], layout: &Layout) -> Result<Self, ModifierError> {
+        let active = modifiers
+            .iter()
+            .map(|m| {
+                Ok(match m {
+                    Modifier::InvertAxis { axis } => Active::Invert(axis_index(layout, axis)?),
+                    Modifier::SwapButtons { a, b } => {
+                        Active::Swap(button_index(layout, a)?, button_index(layout, b)?)
+                    }
+                    Modifier::Sticky { button } => Active::Sticky {
+                        index: button_index(layout, button)?,
+                        held: false,
+                        latched: false,
+                    },
+                    Modifier::Turbo { button, rate_hz } => Active::Turbo {
+                        index: button_index(layout, button)?,
+                        half_period: Duration::from_secs_f32(0.5 / rate_hz),
+                        since: None,
+                    },
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Modifiers {
+            active,
+            enabled: true,
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        for m in self.active.iter_mut() {
+            match m {
+                Active::Sticky { held, latched, .. } => {
+                    *held = false;
+                    *latched = false;
+                }
+                Active::Turbo { since, .. } => *since = None,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn apply(&mut self, frame: &mut Frame, now: Instant) {
+        if !self.enabled {
+            return;
+        }
+        for m in self.active.iter_mut() {
+            match m {
+                Active::Invert(i) => frame.axes[*i] = -frame.axes[*i],
+                Active::Swap(a, b) => frame.buttons.swap(*a, *b),
+                Active::Sticky {
+                    index,
+                    held,
+                    latched,
+                } => {
+                    let pressed = frame.buttons[*index];
+                    if pressed && !*held {
+                        *latched = !*latched;
+                    }
+                    *held = pressed;
+                    frame.buttons[*index] = *latched;
+                }
+                Active::Turbo {
+                    index,
+                    half_period,
+                    since,
+                } => {
+                    if !frame.buttons[*index] {
+                        *since = None;
+                        continue;
+                    }
+                    let start = *since.get_or_insert(now);
+                    let phase = now.duration_since(start).as_nanos() / half_period.as_nanos();
+                    frame.buttons[*index] = phase.is_multiple_of(2);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(layout: &Layout) -> Frame {
+        Frame {
+            axes: vec![0f32; layout.axes.len()],
+            buttons: vec![false; layout.buttons.len()],
+        }
+    }
+
+    #[test]
+    fn modifiers() {
+        let layout = Layout::load("../resources/layouts/snes.json");
+        let json = r#"[
+            {"modifier": "invert_axis", "axis": "Xright"},
+            {"modifier": "swap_buttons", "a": "A", "b": "B"},
+            {"modifier": "sticky", "button": "X"},
+            {"modifier": "turbo", "button": "Y", "rate_hz": 5}
+        ]"#;
+        let config: Vec<Modifier> = serde_json::from_str(json).unwrap();
+        let mut modifiers = Modifiers::new(&config, &layout).unwrap();
+        let (a, b, x, y) = (0, 1, 2, 3);
+        let start = Instant::now();
+
+        let mut f = frame(&layout);
+        f.axes[0] = 0.5;
+        f.buttons[a] = true;
+        f.buttons[x] = true;
+        f.buttons[y] = true;
+        modifiers.apply(&mut f, start);
+        assert_eq!(f.axes[0], -0.5);
+        assert!(!f.buttons[a] && f.buttons[b]);
+        assert!(f.buttons[x]);
+        assert!(f.buttons[y]);
+
+        // Sticky stays latched after release, turbo drops out after half a period
+        let mut f = frame(&layout);
+        f.buttons[y] = true;
+        modifiers.apply(&mut f, start + Duration::from_millis(150));
+        assert!(f.buttons[x]);
+        assert!(!f.buttons[y]);
+
+        modifiers.toggle();
+        let mut f = frame(&layout);
+        f.axes[0] = 0.5;
+        modifiers.apply(&mut f, start);
+        assert_eq!(f.axes[0], 0.5);
+        assert!(!f.buttons[x]);
+
+        let unknown = vec![Modifier::Sticky {
+            button: "Z".to_string(),
+        }];
+        assert!(matches!(
+            Modifiers::new(&unknown, &layout),
+            Err(ModifierError::UnknownButton(name)) if name == "Z"
+        ));
+
+        let turbo = |rate: &str| {
+            let json = format!(
+                r#"{{"modifier": "turbo", "button": "Y", "rate_hz": {}}}"#,
+                rate
+            );
+            serde_json::from_str::<Modifier>(&json)
+        };
+        assert!(turbo("0").is_err());
+        assert!(turbo("-5").is_err());
+        assert!(turbo("1e12").is_err());
+        assert!(turbo("1000").is_ok());
+    }
+}
@@ -1,9 +1,21 @@
-use crate::joypaths;
+use crate::aggregate;
+use crate::connection::{self, Connections, MissingPolicy};
+use crate::consensus::ConsensusWindow;
+use crate::curve::AxisResponses;
+use crate::delay;
+use crate::hysteresis::ButtonLatch;
+use crate::input::InputSource;
+use crate::layout::Layout;
+use crate::modifiers;
+use crate::output::{self, Output};
+use crate::rumble::{Rumble, RumbleConfig};
+use crate::split;
+use crate::turns;
+use crate::Config;
 use crate::Team;
 use crate::TeamLock;
-use gilrs;
-use software_joystick::*;
-use strum::IntoEnumIterator;
+use mjoy_gui::gui::feedback_info::Connection;
+use std::time::{Duration, Instant};
 
 pub struct Outjoys<'a> {
     pub outjoys: Vec<Outjoy<'a>>,
@@ -11,70 +23,142 @@ pub struct Outjoys<'a> {
 
 pub struct Outjoy<'a> {
     team: &'a Team,
-    joy: Joystick,
+    layout: &'a Layout,
+    output: Box<dyn Output>,
+    aggregation: aggregate::TeamAggregation,
+    response: AxisResponses,
+    turns: turns::Turns,
+    delay: delay::DelayLine<Frame>,
+    modifiers: modifiers::Modifiers,
+    rumble: RumbleConfig,
+    latches: Vec<ButtonLatch>,
+    windows: Vec<ConsensusWindow>,
 }
 
-fn inbutton_to_outbutton(b: &crate::injoy::NamedButton) -> software_joystick::Button {
-    use crate::injoy::NamedButton;
-    match b {
-        NamedButton::X => software_joystick::Button::RightNorth,
-        NamedButton::A => software_joystick::Button::RightEast,
-        NamedButton::B => software_joystick::Button::RightSouth,
-        NamedButton::Y => software_joystick::Button::RightWest,
-        NamedButton::L => software_joystick::Button::L1,
-        NamedButton::R => software_joystick::Button::R1,
-        NamedButton::Start => software_joystick::Button::RightSpecial,
-        NamedButton::Select => software_joystick::Button::LeftSpecial,
-    }
-}
-
-fn inaxis_to_outaxis(a: &crate::injoy::NamedAxis) -> software_joystick::Axis {
-    use crate::injoy::NamedAxis;
-    match a {
-        NamedAxis::Xright => software_joystick::Axis::X,
-        NamedAxis::Yup => software_joystick::Axis::Y,
-    }
+// Aggregated team output, indexed like the layout's axes and buttons. Axes run
+// from -1 to 1 with positive right and up; outputs convert from that.
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    pub axes: Vec<f32>,
+    pub buttons: Vec<bool>,
 }
 
 impl<'a> Outjoy<'a> {
-    pub fn new(team: &'a Team, index: u32) -> Self {
-        let joy = Joystick::new(format!("Buster{}", index)).unwrap();
-        Self { team, joy }
+    // Settings come from the config's per-team lists, by output index. Frames
+    // go to `output`, which `output::for_team` opens as configured.
+    pub fn new(
+        team: &'a Team,
+        layout: &'a Layout,
+        config: &Config,
+        out_index: u32,
+        output: Box<dyn Output>,
+    ) -> Self {
+        let i = out_index as usize;
+        let aggregation = config.team_aggregation.get(i).cloned().unwrap_or_default();
+        let mode = config.team_modes.get(i).cloned().unwrap_or_default();
+        let delay = config.team_delays.get(i).cloned().unwrap_or_default();
+        let modifiers = config
+            .team_modifiers
+            .get(i)
+            .map(|m| m.as_slice())
+            .unwrap_or_default();
+        let rumble = config.team_rumble.get(i).cloned().unwrap_or_default();
+        let turns = turns::Turns::new(mode, team.players.len());
+        let delay = delay::DelayLine::new(delay, rand::random());
+        let modifiers = modifiers::Modifiers::new(modifiers, layout)
+            .expect("Modifiers are checked when the config loads");
+        for button in layout.buttons.iter() {
+            let aggregation = aggregation.for_button(&button.name);
+            if layout.hysteresis(button).has_bands() && !aggregation.uses_threshold() {
+                eprintln!(
+                    "Team {}: hysteresis bands do nothing for {} with {:?} aggregation",
+                    team.name, button.name, aggregation
+                );
+            }
+        }
+        Self {
+            team,
+            layout,
+            output,
+            aggregation,
+            response: config.axis_response.clone(),
+            turns,
+            delay,
+            modifiers,
+            rumble,
+            latches: layout
+                .buttons
+                .iter()
+                .map(|_| ButtonLatch::default())
+                .collect(),
+            windows: layout
+                .buttons
+                .iter()
+                .map(|_| ConsensusWindow::default())
+                .collect(),
+        }
     }
 
-    fn inaxis_to_letter(a: &crate::injoy::NamedAxis, f: f32) -> Option<String> {
-        use crate::injoy::NamedAxis;
-        match a {
-            NamedAxis::Xright => match f {
-                f if f > 0.1 => Some(">".to_string()),
-                f if f < -0.1 => Some("<".to_string()),
-                _ => None,
-            },
-            NamedAxis::Yup => match f {
-                f if f > 0.1 => Some("^".to_string()),
-                f if f < -0.1 => Some("v".to_string()),
-                _ => None,
-            },
+    fn is_active(&self, common_name: &str) -> bool {
+        match self.team.players.iter().position(|p| p == common_name) {
+            Some(i) => self.turns.is_active(i),
+            None => false,
         }
     }
 
-    fn inbutton_to_letter(b: &crate::injoy::NamedButton) -> String {
-        use crate::injoy::NamedButton;
-        match b {
-            NamedButton::X => "X".to_string(),
-            NamedButton::A => "A".to_string(),
-            NamedButton::B => "B".to_string(),
-            NamedButton::Y => "Y".to_string(),
-            NamedButton::L => "L".to_string(),
-            NamedButton::R => "R".to_string(),
-            NamedButton::Start => "t".to_string(),
-            NamedButton::Select => "e".to_string(),
+    fn pass_pressed(&self, context: &UpdateContext) -> bool {
+        let control = match self.turns.pass_button() {
+            Some(name) => self.layout.buttons.iter().find(|b| b.name == name),
+            None => None,
+        };
+        let control = match control {
+            Some(control) => control,
+            None => return false,
+        };
+        // An empty team has nobody to take a turn
+        let active = match self
+            .turns
+            .active_index()
+            .and_then(|i| self.team.players.get(i))
+        {
+            Some(active) => active,
+            None => return false,
+        };
+
+        crate::injoy::button_value(context.input, active, control) > 0.5
+    }
+
+    fn update_turns(&mut self, context: &mut UpdateContext) {
+        let pressed = self.pass_pressed(context);
+        let connected: Vec<bool> = self
+            .team
+            .players
+            .iter()
+            .map(|p| context.input.connected(p))
+            .collect();
+        self.turns.update(pressed, &connected, context.now);
+
+        for team in context.feedback.teams.iter_mut() {
+            if self.team.name != team.team_name {
+                continue;
+            }
+            team.modifiers_enabled = self.modifiers.enabled();
+            for p in team.players.iter_mut() {
+                p.active = self.is_active(&p.player_name);
+                p.connection = match context.connections.status(&p.player_name) {
+                    connection::Status::Connected => Connection::Connected,
+                    connection::Status::Missing(_) => Connection::Reconnecting,
+                    connection::Status::Gone => Connection::Disconnected,
+                };
+            }
         }
     }
 
-    fn update_axes<'b, 'c, 'd, 'e, 'f>(&'a self, context: &'d mut UpdateContext<'b, 'c, 'e, 'f>) {
-        use crate::injoy::NamedAxis;
+    pub fn toggle_modifiers(&mut self) {
+        self.modifiers.toggle();
+    }
 
+    fn update_axes(&self, context: &mut UpdateContext, frame: &mut Frame) {
         let mut fb_team = None;
         for team in context.feedback.teams.iter_mut() {
             if self.team.name == team.team_name {
@@ -83,105 +167,84 @@ impl<'a> Outjoy<'a> {
             }
         }
 
-        let lefties = ["<".to_string(), ">".to_string()];
-        let upies = ["^".to_string(), "v".to_string()];
+        for inaxis in self.layout.axes.iter() {
+            let mut samples = Vec::new();
+            let mut source = crate::injoy::AxisSource::Dpad;
 
-        for (i, inaxis) in crate::injoy::NamedAxis::iter().enumerate() {
-            let mut sum = 0 as f32;
-            let mut count = 0;
+            let clearem = inaxis.all_glyphs();
 
-            let out_axis = inaxis_to_outaxis(&inaxis);
-
-            let clearem = match inaxis {
-                NamedAxis::Xright => &lefties,
-                NamedAxis::Yup => &upies,
-            };
-
-            for (_id, gamepad) in context.gilrs.gamepads() {
-                let devpath = gamepad.devpath();
-                let namedpath = &context.event_path_lookup.0.get(devpath);
-                if namedpath.is_none() {
+            for common_name in self.team.players.iter() {
+                if !context.input.connected(common_name) {
                     continue;
                 }
-                let namedpath = namedpath.unwrap();
-                let common_name = &namedpath.common_name;
-
-                if self.team.players.contains(&common_name) {
-                    let (axis_id, scalar) = crate::injoy::snes_namedaxis_to_id_and_scalar(&inaxis);
-                    let gilrs_axis = match axis_id {
-                        0 => gilrs::Button::DPadRight,
-                        1 => gilrs::Button::DPadUp,
-                        _ => panic!("Invalid axis_id"),
-                    };
 
-                    let value = gamepad.button_data(gilrs_axis);
-                    let value = match value {
-                        Some(value) => {
-                            let vv = value.value();
-                            let vvv = match vv {
-                                v if v < 0.1 => -1,
-                                v if v > 0.9 => 1,
-                                _ => 0,
-                            } as f32;
-                            vvv
-                        }
-                        None => 0 as f32,
-                    };
-                    let value = value * scalar.signum();
-                    sum += value;
-                    count += 1;
-
-                    let letter = Self::inaxis_to_letter(&inaxis, value);
+                if !split::owns(&self.team.controls, common_name, &inaxis.name) {
+                    continue;
+                }
 
-                    let fb_team = match fb_team.as_mut() {
-                        Some(fb_team) => fb_team,
-                        None => continue,
-                    };
-                    let mut player = None;
-                    for p in fb_team.players.iter_mut() {
-                        if &p.player_name == common_name {
-                            player = Some(p);
-                            break;
-                        }
+                let value = crate::injoy::axis_value(
+                    context.input,
+                    common_name,
+                    inaxis,
+                    context.axis_sources,
+                );
+                if self.is_active(common_name) {
+                    samples.push(aggregate::Sample {
+                        player: common_name,
+                        value,
+                    });
+                    let player_source = crate::injoy::axis_source(
+                        context.input,
+                        common_name,
+                        inaxis,
+                        context.axis_sources,
+                    );
+                    if player_source == crate::injoy::AxisSource::Analog {
+                        source = player_source;
                     }
+                }
 
-                    if player.is_none() {
-                        continue;
+                let letter = inaxis.glyph(value);
+
+                let fb_team = match fb_team.as_mut() {
+                    Some(fb_team) => fb_team,
+                    None => continue,
+                };
+                let mut player = None;
+                for p in fb_team.players.iter_mut() {
+                    if &p.player_name == common_name {
+                        player = Some(p);
+                        break;
                     }
-                    let player = player.unwrap();
+                }
 
-                    for f in player.feedback.0.iter_mut() {
-                        if clearem.contains(&f.button) {
-                            f.state = mjoy_gui::gui::feedback_info::PressState::Unpressed;
-                        }
-                    }
+                if player.is_none() {
+                    continue;
+                }
+                let player = player.unwrap();
 
-                    if letter.is_none() {
-                        continue;
+                for f in player.feedback.0.iter_mut() {
+                    if clearem.contains(&f.button) {
+                        f.state = mjoy_gui::gui::feedback_info::PressState::Unpressed;
                     }
-                    let letter = letter.unwrap();
-                    for f in player.feedback.0.iter_mut() {
-                        if f.button == letter {
-                            f.state = mjoy_gui::gui::feedback_info::PressState::Pressed;
-                        }
+                }
+
+                if letter.is_none() {
+                    continue;
+                }
+                let letter = letter.unwrap();
+                for f in player.feedback.0.iter_mut() {
+                    if f.button == letter {
+                        f.state = mjoy_gui::gui::feedback_info::PressState::Pressed;
                     }
                 }
             }
 
-            let average = match count {
-                0 => {
-                    eprintln!("No players found for team {}", self.team.name);
-                    0 as f32
-                }
-                _ => sum / count as f32,
-            };
-            let average = average.clamp(-1.0f32, 1.0f32);
-            let pow = average.abs().powf(2.0f32);
-            let average = average.signum() * pow;
-            let average_i = (average * 512f32) as i32;
-            self.joy.move_axis(out_axis, average_i).unwrap();
+            let average = self.aggregation.for_axis(&inaxis.name).axis(&samples);
+            let average = self.response.for_axis(&inaxis.name, source).apply(average);
+            frame.axes.push(average);
 
-            let letter = Self::inaxis_to_letter(&inaxis, average);
+            let letter = inaxis.glyph(average);
             let fb_team = match fb_team.as_mut() {
                 Some(fb_team) => fb_team,
                 None => continue,
@@ -205,110 +268,96 @@ impl<'a> Outjoy<'a> {
         }
     }
 
-    fn update_buttons<'b, 'c, 'd, 'e, 'f>(
-        &'a self,
-        context: &'d mut UpdateContext<'b, 'c, 'e, 'f>,
-    ) {
-        use crate::injoy::NamedButton;
+    fn update_buttons(&mut self, context: &mut UpdateContext, frame: &mut Frame) {
+        let threshold = context.thresholds.for_team(self.team.out_index as usize);
 
         let mut fb_team = None;
         for team in context.feedback.teams.iter_mut() {
             if self.team.name == team.team_name {
+                team.threshold = Some(threshold);
                 fb_team = Some(team);
                 break;
             }
         }
 
-        for (i, inbutton) in crate::injoy::NamedButton::iter().enumerate() {
-            let mut sum = 0 as f32;
-            let mut count = 0;
+        let now = context.now;
+        for (index, inbutton) in self.layout.buttons.iter().enumerate() {
+            let mut samples = Vec::new();
 
-            let outbutton = inbutton_to_outbutton(&inbutton);
-
-            for (_id, gamepad) in context.gilrs.gamepads() {
-                let devpath = gamepad.devpath();
-                let namedpath = &context.event_path_lookup.0.get(devpath);
-                if namedpath.is_none() {
+            for common_name in self.team.players.iter() {
+                if !context.input.connected(common_name) {
                     continue;
                 }
-                let namedpath = namedpath.unwrap();
-                let common_name = &namedpath.common_name;
-
-                if context.stick_only_names.contains(&common_name) {
+                if !split::owns(&self.team.controls, common_name, &inbutton.name) {
                     continue;
                 }
 
-                if self.team.players.contains(&common_name) {
-                    let button_id: gilrs::Button = crate::injoy::snes_namedbutton_to_id(&inbutton);
-                    let value = gamepad.button_data(button_id);
-                    let value = match value {
-                        Some(value) => {
-                            let vv = value.value();
-                            let vvv = match vv {
-                                v if v < 0.1 => 0,
-                                v if v > 0.9 => 1,
-                                _ => 0,
-                            } as f32;
-                            vvv
-                        }
-                        None => 0 as f32,
-                    };
+                let value = crate::injoy::button_value(context.input, common_name, inbutton);
 
-                    sum += value;
-                    count += 1;
+                if self.is_active(common_name) {
+                    samples.push(aggregate::Sample {
+                        player: common_name,
+                        value,
+                    });
+                }
 
-                    let fb_team = match fb_team.as_mut() {
-                        Some(fb_team) => fb_team,
-                        None => continue,
-                    };
-                    let mut player = None;
-                    for p in fb_team.players.iter_mut() {
-                        if &p.player_name == common_name {
-                            player = Some(p);
-                            break;
-                        }
+                let fb_team = match fb_team.as_mut() {
+                    Some(fb_team) => fb_team,
+                    None => continue,
+                };
+                let mut player = None;
+                for p in fb_team.players.iter_mut() {
+                    if &p.player_name == common_name {
+                        player = Some(p);
+                        break;
                     }
+                }
 
-                    if player.is_none() {
-                        continue;
-                    }
-                    let player = player.unwrap();
-
-                    let letter = Self::inbutton_to_letter(&inbutton);
-                    for f in player.feedback.0.iter_mut() {
-                        if f.button == letter {
-                            let punp = if value > context.button_threshold {
-                                mjoy_gui::gui::feedback_info::PressState::Pressed
-                            } else {
-                                mjoy_gui::gui::feedback_info::PressState::Unpressed
-                            };
-                            f.state = punp;
-                        }
+                if player.is_none() {
+                    continue;
+                }
+                let player = player.unwrap();
+
+                for f in player.feedback.0.iter_mut() {
+                    if f.button == inbutton.glyph {
+                        let punp = if value > threshold {
+                            mjoy_gui::gui::feedback_info::PressState::Pressed
+                        } else {
+                            mjoy_gui::gui::feedback_info::PressState::Unpressed
+                        };
+                        f.state = punp;
                     }
                 }
             }
 
-            let average = match count {
-                0 => {
-                    eprintln!("No players found for team");
-                    0 as f32
-                }
-                _ => sum / count as f32,
-            };
-
-            self.joy
-                .button_press(outbutton, average > context.button_threshold)
-                .unwrap();
+            let aggregation = self.aggregation.for_button(&inbutton.name);
+            let window = &mut self.windows[index];
+            let pressed = self.latches[index].update(
+                self.layout.hysteresis(inbutton),
+                threshold,
+                now,
+                |cut| match aggregation {
+                    aggregate::Aggregation::Window { window_ms, hold_ms } => window.update(
+                        &samples,
+                        cut,
+                        Duration::from_millis(*window_ms),
+                        Duration::from_millis(*hold_ms),
+                        now,
+                    ),
+                    _ => aggregation.button(&samples, cut),
+                },
+            );
+
+            frame.buttons.push(pressed);
 
             let fb_team = match fb_team.as_mut() {
                 Some(fb_team) => fb_team,
                 None => continue,
             };
 
-            let letter = Self::inbutton_to_letter(&inbutton);
             for f in fb_team.feedback.0.iter_mut() {
-                if f.button == letter {
-                    let punp = if average > context.button_threshold {
+                if f.button == inbutton.glyph {
+                    let punp = if pressed {
                         mjoy_gui::gui::feedback_info::PressState::Pressed
                     } else {
                         mjoy_gui::gui::feedback_info::PressState::Unpressed
@@ -319,33 +368,252 @@ impl<'a> Outjoy<'a> {
         }
     }
 
-    pub fn update<'b, 'c, 'd, 'e, 'f>(&'a self, context: &'d mut UpdateContext<'b, 'c, 'e, 'f>) {
-        self.update_axes(context);
-        self.update_buttons(context);
-        self.joy.synchronise().unwrap();
+    pub fn update(&mut self, context: &mut UpdateContext) {
+        self.update_turns(context);
+        let mut frame = Frame::default();
+        self.update_axes(context, &mut frame);
+        self.update_buttons(context, &mut frame);
+
+        let now = context.now;
+        self.modifiers.apply(&mut frame, now);
+
+        let waiting = context.connections.waiting_for_any(&self.team.players);
+        let frame = match context.connections.policy() {
+            MissingPolicy::Freeze if waiting => None,
+            MissingPolicy::Neutral if waiting => Some(Frame {
+                axes: vec![0f32; frame.axes.len()],
+                buttons: vec![false; frame.buttons.len()],
+            }),
+            _ => Some(frame),
+        };
+        if let Some(frame) = frame {
+            self.delay.push(frame, now);
+        }
+        if let Some(frame) = self.delay.pop(now) {
+            self.output.write(self.layout, &frame);
+        }
+        self.output.synchronise();
+        self.forward_rumble(context);
+    }
+
+    // Whatever the game plays on the team's output is felt by every player
+    fn forward_rumble(&mut self, context: &mut UpdateContext) {
+        for rumble in self.output.rumble() {
+            if self.rumble.gain <= 0f32 {
+                continue;
+            }
+            let rumble = rumble.scaled(self.rumble.gain);
+            if self.rumble.log_only {
+                println!("{} rumble {:?}", self.team.name, rumble);
+                continue;
+            }
+            context.rumble.push((self.team.players.clone(), rumble));
+        }
     }
 }
 
 pub struct UpdateContext<'b, 'c, 'e, 'f> {
-    pub event_path_lookup: &'b joypaths::EventPathLookup,
-    pub gilrs: &'c mut gilrs::Gilrs,
+    // The tick's time, which every timed stage runs on
+    pub now: Instant,
+    pub input: &'c dyn InputSource,
     pub feedback: &'e mut mjoy_gui::gui::feedback_info::FeedbackInfo<'f>,
-    pub stick_only_names: &'b Vec<String>,
-    pub button_threshold: f32,
+    pub axis_sources: &'b crate::injoy::AxisSources,
+    pub thresholds: &'b crate::threshold::Thresholds,
+    pub connections: &'b Connections,
+    // Rumble to play on these players' controllers
+    pub rumble: &'e mut Vec<(Vec<String>, Rumble)>,
 }
 
 impl<'a> Outjoys<'a> {
-    pub fn new(tl: &'a TeamLock) -> Self {
-        let mut outjoys = Vec::new();
-        for team in tl.teams.iter() {
-            outjoys.push(Outjoy::new(team, team.out_index));
-        }
+    pub fn new(tl: &'a TeamLock, layout: &'a Layout, config: &Config) -> Self {
+        let outputs = tl
+            .teams
+            .iter()
+            .map(|team| output::for_team(config, layout, team.out_index))
+            .collect();
+        Self::with_outputs(tl, layout, config, outputs)
+    }
+
+    // One output per team, in teamlock order
+    pub fn with_outputs(
+        tl: &'a TeamLock,
+        layout: &'a Layout,
+        config: &Config,
+        outputs: Vec<Box<dyn Output>>,
+    ) -> Self {
+        let outjoys = tl
+            .teams
+            .iter()
+            .zip(outputs)
+            .map(|(team, output)| Outjoy::new(team, layout, config, team.out_index, output))
+            .collect();
         Self { outjoys }
     }
 
-    pub fn update<'b, 'c, 'd, 'e, 'f>(&'a self, context: &'d mut UpdateContext<'b, 'c, 'e, 'f>) {
-        for outjoy in self.outjoys.iter() {
+    pub fn toggle_modifiers(&mut self, team_index: usize) {
+        if let Some(outjoy) = self.outjoys.get_mut(team_index) {
+            outjoy.toggle_modifiers();
+        }
+    }
+
+    pub fn update(&mut self, context: &mut UpdateContext) {
+        for outjoy in self.outjoys.iter_mut() {
             outjoy.update(context);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{ScriptedInput, Timeline};
+    use mjoy_gui::gui::feedback_info::{self, FeedbackInfo, PressState};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Keeps every frame written, shared with the test through the Rc
+    #[derive(Clone, Default)]
+    struct Recorder(Rc<RefCell<Vec<Frame>>>);
+
+    impl Output for Recorder {
+        fn write(&mut self, _layout: &Layout, frame: &Frame) {
+            self.0.borrow_mut().push(frame.clone());
+        }
+    }
+
+    fn feedback<'f>(tl: &'f TeamLock, layout: &Layout) -> FeedbackInfo<'f> {
+        let presses = feedback_info::Presses(
+            layout
+                .feedback_glyphs()
+                .into_iter()
+                .map(|button| feedback_info::ButtonPress {
+                    button,
+                    state: PressState::Unpressed,
+                })
+                .collect(),
+        );
+        let teams = tl
+            .teams
+            .iter()
+            .map(|team| feedback_info::Team {
+                team_name: &team.name,
+                players: team
+                    .players
+                    .iter()
+                    .map(|player| feedback_info::Player {
+                        player_name: player.clone(),
+                        feedback: presses.clone(),
+                        active: true,
+                        assigned: None,
+                        connection: Connection::Connected,
+                    })
+                    .collect(),
+                feedback: presses.clone(),
+                threshold: None,
+                modifiers: Vec::new(),
+                modifiers_enabled: true,
+            })
+            .collect();
+        FeedbackInfo { teams }
+    }
+
+    fn state(presses: &feedback_info::Presses, glyph: &str) -> bool {
+        presses
+            .0
+            .iter()
+            .any(|p| p.button == glyph && p.state == PressState::Pressed)
+    }
+
+    #[test]
+    fn scripted_team_frames_and_feedback() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "team_allocation": [2],
+                "path_hash_salt": 0,
+                "team_hash_salt": 0,
+                "path_common_name_max_length": 12,
+                "hat_only_players": [],
+                "number_of_multi_port_controllers_to_use": 4,
+                "threshold": {"schedule": {"mode": "fixed", "value": 0.6}}
+            }"#,
+        )
+        .unwrap();
+        let timeline: Timeline = serde_json::from_str(
+            r#"{
+                "players": [{"name": "ann"}, {"name": "bob"}],
+                "events": [
+                    {"at_ms": 0, "player": "ann", "button": "East", "value": 1.0},
+                    {"at_ms": 0, "player": "ann", "button": "DPadRight", "value": 1.0},
+                    {"at_ms": 0, "player": "bob", "button": "DPadRight", "value": 0.5},
+                    {"at_ms": 100, "player": "bob", "button": "East", "value": 1.0},
+                    {"at_ms": 100, "player": "bob", "button": "DPadRight", "value": 1.0},
+                    {"at_ms": 200, "player": "bob", "connected": false}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let layout = Layout::load("../resources/layouts/snes.json");
+        let players = vec!["ann".to_string(), "bob".to_string()];
+        let tl = TeamLock {
+            teams: vec![Team {
+                name: "reds".to_string(),
+                players: players.clone(),
+                out_index: 0,
+                controls: Default::default(),
+            }],
+            seed: None,
+        };
+
+        let recorder = Recorder::default();
+        let mut outjoys =
+            Outjoys::with_outputs(&tl, &layout, &config, vec![Box::new(recorder.clone())]);
+        let mut input = ScriptedInput::new(timeline);
+        let mut feedback = feedback(&tl, &layout);
+        let thresholds = crate::threshold::Thresholds::new(&config.threshold, 1);
+        let mut connections = Connections::new(&config.connection, players);
+        let mut rumble = Vec::new();
+
+        let start = Instant::now();
+        let mut tick = |ms: u64, feedback: &mut FeedbackInfo| {
+            let now = start + Duration::from_millis(ms);
+            input.poll(now);
+            connections.update(&input.players(), now);
+            outjoys.update(&mut UpdateContext {
+                now,
+                input: &input,
+                feedback,
+                axis_sources: &config.axis_sources,
+                thresholds: &thresholds,
+                connections: &connections,
+                rumble: &mut rumble,
+            });
+        };
+
+        // Ann alone is half the team, which is under the threshold
+        tick(0, &mut feedback);
+        let team = &feedback.teams[0];
+        assert_eq!(team.threshold, Some(0.6));
+        assert!(state(&team.players[0].feedback, "A"));
+        assert!(!state(&team.players[1].feedback, "A"));
+        assert!(!state(&team.feedback, "A"));
+        assert!(state(&team.feedback, ">"));
+
+        // With bob both agree
+        tick(100, &mut feedback);
+        assert!(state(&feedback.teams[0].feedback, "A"));
+
+        // Bob drops, so ann carries the team alone
+        tick(200, &mut feedback);
+        let team = &feedback.teams[0];
+        assert!(team.players[1].connection == Connection::Reconnecting);
+        assert!(state(&team.feedback, "A"));
+
+        let frames = recorder.0.borrow();
+        assert_eq!(frames.len(), 3);
+        assert!(frames[0].axes[0] > 0f32 && frames[0].axes[0] < 1f32);
+        assert!(!frames[0].buttons[0]);
+        assert_eq!(frames[1].axes[0], 1f32);
+        assert!(frames[1].buttons[0]);
+        assert!(frames[2].buttons[0]);
+    }
+}
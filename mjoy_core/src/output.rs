@@ -0,0 +1,74 @@
+use crate::dolphin::DolphinPipe;
+use crate::keyboard::{Keyboard, Keymap};
+use crate::layout::Layout;
+use crate::outjoy::Frame;
+use crate::rumble::Rumble;
+use crate::uinput;
+use crate::Config;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum OutputConfig {
+    // A virtual joystick per team, needs write access to /dev/uinput
+    #[default]
+    Uinput,
+    // A named pipe per team in Dolphin's Pipes directory
+    Dolphin {
+        // Defaults to ~/.local/share/dolphin-emu/Pipes
+        #[serde(default)]
+        pipe_dir: Option<String>,
+    },
+    // A virtual keyboard per team, for games that bind keys better than joysticks
+    Keyboard {
+        #[serde(default)]
+        keymap: Keymap,
+    },
+}
+
+// Where a team's frames end up
+pub trait Output {
+    fn write(&mut self, layout: &Layout, frame: &Frame);
+    // Called once per tick, whether or not a frame was written
+    fn synchronise(&mut self) {}
+    // Rumble games have played on the output since the last call
+    fn rumble(&mut self) -> Vec<Rumble> {
+        Vec::new()
+    }
+}
+
+// The team's entry in `team_outputs` and `team_devices`, else the defaults
+pub fn for_team(config: &Config, layout: &Layout, out_index: u32) -> Box<dyn Output> {
+    let i = out_index as usize;
+    let device = config.team_devices.get(i).cloned().unwrap_or_default();
+    let output = config.team_outputs.get(i).unwrap_or(&config.output);
+    open(output, &device, layout, out_index)
+}
+
+pub fn open(
+    config: &OutputConfig,
+    device: &uinput::DeviceConfig,
+    layout: &Layout,
+    index: u32,
+) -> Box<dyn Output> {
+    let name = device.name(index);
+    match config {
+        OutputConfig::Uinput => Box::new(
+            uinput::Device::new(device, &name).expect("Failed to create the virtual joystick"),
+        ),
+        OutputConfig::Dolphin { pipe_dir } => {
+            let pipe_dir = match pipe_dir {
+                Some(pipe_dir) => pipe_dir.clone(),
+                None => format!(
+                    "{}/.local/share/dolphin-emu/Pipes",
+                    std::env::var("HOME").unwrap_or_default()
+                ),
+            };
+            Box::new(DolphinPipe::new(&pipe_dir, &name))
+        }
+        OutputConfig::Keyboard { keymap } => Box::new(
+            Keyboard::new(keymap, layout, device, &name)
+                .expect("Failed to create the virtual keyboard"),
+        ),
+    }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RumbleConfig {
+    // Scales every effect, 0 turns rumble off for the team
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+    // Print effects instead of playing them
+    #[serde(default)]
+    pub log_only: bool,
+}
+
+impl Default for RumbleConfig {
+    fn default() -> Self {
+        RumbleConfig {
+            gain: default_gain(),
+            log_only: false,
+        }
+    }
+}
+
+// Motor strengths from 0 to 1. A duration of None lasts until the next rumble.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rumble {
+    pub strong: f32,
+    pub weak: f32,
+    pub duration: Option<Duration>,
+}
+
+impl Rumble {
+    pub fn stop() -> Self {
+        Rumble {
+            strong: 0f32,
+            weak: 0f32,
+            duration: None,
+        }
+    }
+
+    pub fn is_stop(&self) -> bool {
+        self.strong <= 0f32 && self.weak <= 0f32
+    }
+
+    pub fn scaled(&self, gain: f32) -> Self {
+        Rumble {
+            strong: (self.strong * gain).clamp(0f32, 1f32),
+            weak: (self.weak * gain).clamp(0f32, 1f32),
+            duration: self.duration,
+        }
+    }
+}
+
+// Rumble effects a game has uploaded to a virtual pad, by effect id
+pub struct Effects {
+    uploaded: HashMap<i16, Rumble>,
+    gain: f32,
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Effects {
+            uploaded: HashMap::new(),
+            gain: 1f32,
+        }
+    }
+}
+
+impl Effects {
+    // Magnitudes and gain are 0..0xffff and length is in ms, as the kernel has them
+    pub fn upload(&mut self, id: i16, strong: u16, weak: u16, length_ms: u16) {
+        let rumble = Rumble {
+            strong: strong as f32 / u16::MAX as f32,
+            weak: weak as f32 / u16::MAX as f32,
+            duration: match length_ms {
+                0 => None,
+                ms => Some(Duration::from_millis(ms as u64)),
+            },
+        };
+        self.uploaded.insert(id, rumble);
+    }
+
+    pub fn erase(&mut self, id: i16) {
+        self.uploaded.remove(&id);
+    }
+
+    pub fn set_gain(&mut self, gain: u16) {
+        self.gain = gain as f32 / u16::MAX as f32;
+    }
+
+    // A play count of 0 stops the effect
+    pub fn play(&self, id: i16, count: i32) -> Option<Rumble> {
+        let rumble = self.uploaded.get(&id)?;
+        if count <= 0 {
+            return Some(Rumble::stop());
+        }
+        Some(rumble.scaled(self.gain))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uploaded_effects() {
+        let mut effects = Effects::default();
+        effects.upload(0, u16::MAX, 0, 250);
+        effects.upload(1, 0, u16::MAX / 2, 0);
+        assert_eq!(effects.play(7, 1), None);
+
+        let strong = effects.play(0, 1).unwrap();
+        assert_eq!(strong.strong, 1.0);
+        assert_eq!(strong.duration, Some(Duration::from_millis(250)));
+        assert!(effects.play(0, 0).unwrap().is_stop());
+
+        effects.set_gain(u16::MAX / 2);
+        let weak = effects.play(1, 1).unwrap().scaled(2.0);
+        assert_eq!(weak.duration, None);
+        assert!((weak.weak - 0.5).abs() < 0.01);
+
+        effects.erase(1);
+        assert_eq!(effects.play(1, 1), None);
+    }
+}
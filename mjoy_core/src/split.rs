@@ -0,0 +1,81 @@
+use crate::layout::Layout;
+use std::collections::HashMap;
+
+// Player name -> control names that player drives. Players without an entry own every control.
+pub type ControlOwnership = HashMap<String, Vec<String>>;
+
+pub fn owns(ownership: &ControlOwnership, player: &str, control: &str) -> bool {
+    match ownership.get(player) {
+        Some(controls) => controls.iter().any(|c| c == control),
+        None => true,
+    }
+}
+
+// Splits are listed per player slot, in the same order the team's players were assigned
+pub fn assign(players: &[String], splits: Option<&Vec<Vec<String>>>) -> ControlOwnership {
+    let mut ownership = HashMap::new();
+    let splits = match splits {
+        Some(splits) => splits,
+        None => return ownership,
+    };
+    for (player, controls) in players.iter().zip(splits.iter()) {
+        ownership.insert(player.clone(), controls.clone());
+    }
+    ownership
+}
+
+// hat_only_players from the config are players that only own the axes
+pub fn apply_hat_only(
+    ownership: &mut ControlOwnership,
+    players: &[String],
+    hat_only: &[String],
+    layout: &Layout,
+) {
+    for player in players.iter() {
+        if !hat_only.contains(player) || ownership.contains_key(player) {
+            continue;
+        }
+        let axes = layout.axes.iter().map(|a| a.name.clone()).collect();
+        ownership.insert(player.clone(), axes);
+    }
+}
+
+pub fn glyphs(controls: &[String], layout: &Layout) -> Vec<String> {
+    let mut glyphs = Vec::new();
+    for axis in layout.axes.iter() {
+        if controls.contains(&axis.name) {
+            glyphs.extend(axis.all_glyphs());
+        }
+    }
+    for button in layout.buttons.iter() {
+        if controls.contains(&button.name) {
+            glyphs.push(button.glyph.clone());
+        }
+    }
+    glyphs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ownership() {
+        let layout = Layout::load("../resources/layouts/snes.json");
+        let players: Vec<String> = ["p1", "p2", "p3"].iter().map(|s| s.to_string()).collect();
+        let splits = vec![
+            vec!["Xright".to_string(), "Yup".to_string()],
+            vec!["A".to_string(), "B".to_string()],
+        ];
+
+        let mut ownership = assign(&players, Some(&splits));
+        apply_hat_only(&mut ownership, &players, &["p2".to_string()], &layout);
+
+        assert!(owns(&ownership, "p1", "Yup"));
+        assert!(!owns(&ownership, "p1", "A"));
+        assert!(owns(&ownership, "p2", "B"));
+        assert!(!owns(&ownership, "p2", "Yup"));
+        assert!(owns(&ownership, "p3", "Start"));
+        assert_eq!(glyphs(&ownership["p1"], &layout), ["<", ">", "v", "^"]);
+    }
+}
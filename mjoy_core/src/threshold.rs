@@ -0,0 +1,280 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::time::{Duration, Instant};
+
+// Low and high bounds, accepted either way round so drawing from them can't panic
+fn ordered<'de, D, T>(deserializer: D) -> Result<[T; 2], D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + PartialOrd,
+{
+    let [a, b] = <[T; 2]>::deserialize(deserializer)?;
+    Ok(if b < a { [b, a] } else { [a, b] })
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Distribution {
+    Uniform,
+    // Mean of two uniform draws, so values bunch up in the middle of the range
+    Triangular,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Schedule {
+    Fixed {
+        value: f32,
+    },
+    Random {
+        initial: f32,
+        initial_ms: u64,
+        distribution: Distribution,
+        range: [f32; 2],
+        #[serde(deserialize_with = "ordered")]
+        clamp: [f32; 2],
+        #[serde(deserialize_with = "ordered")]
+        interval_ms: [u64; 2],
+    },
+    Stepped {
+        values: Vec<f32>,
+        interval_ms: u64,
+    },
+    Sinusoidal {
+        min: f32,
+        max: f32,
+        period_ms: u64,
+    },
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::Random {
+            initial: 0.9,
+            initial_ms: 1000,
+            distribution: Distribution::Uniform,
+            range: [0.49, 1.10],
+            clamp: [0.0, 0.95],
+            interval_ms: [300, 5300],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThresholdConfig {
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub schedule: Schedule,
+    // When false every team rolls its own threshold
+    #[serde(default = "default_shared")]
+    pub shared: bool,
+    // Per team overrides of `schedule`, only used when not shared
+    #[serde(default)]
+    pub teams: Vec<Schedule>,
+}
+
+fn default_shared() -> bool {
+    true
+}
+
+impl Default for ThresholdConfig {
+    fn default() -> Self {
+        ThresholdConfig {
+            seed: None,
+            schedule: Schedule::default(),
+            shared: default_shared(),
+            teams: Vec::new(),
+        }
+    }
+}
+
+pub struct ThresholdSchedule {
+    schedule: Schedule,
+    rng: StdRng,
+    start: Instant,
+    next_change: Instant,
+    step: usize,
+    current: f32,
+}
+
+impl ThresholdSchedule {
+    pub fn new(schedule: Schedule, seed: u64, now: Instant) -> Self {
+        let (current, next_change) = match &schedule {
+            Schedule::Fixed { value } => (*value, now),
+            Schedule::Random {
+                initial,
+                initial_ms,
+                ..
+            } => (*initial, now + Duration::from_millis(*initial_ms)),
+            Schedule::Stepped {
+                values,
+                interval_ms,
+            } => (
+                values.first().copied().unwrap_or(0.5),
+                now + Duration::from_millis(*interval_ms),
+            ),
+            Schedule::Sinusoidal { min, max, .. } => ((min + max) / 2f32, now),
+        };
+        ThresholdSchedule {
+            schedule,
+            rng: StdRng::seed_from_u64(seed),
+            start: now,
+            next_change,
+            step: 0,
+            current,
+        }
+    }
+
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    pub fn update(&mut self, now: Instant) -> f32 {
+        match &self.schedule {
+            Schedule::Fixed { .. } => {}
+            Schedule::Random {
+                distribution,
+                range,
+                clamp,
+                interval_ms,
+                ..
+            } => {
+                if now >= self.next_change {
+                    let millis = self.rng.gen_range(interval_ms[0]..=interval_ms[1]);
+                    self.next_change += Duration::from_millis(millis);
+                    let unit = match distribution {
+                        Distribution::Uniform => self.rng.gen::<f32>(),
+                        Distribution::Triangular => {
+                            (self.rng.gen::<f32>() + self.rng.gen::<f32>()) / 2f32
+                        }
+                    };
+                    let value = range[0] + unit * (range[1] - range[0]);
+                    self.current = value.clamp(clamp[0], clamp[1]);
+                }
+            }
+            Schedule::Stepped {
+                values,
+                interval_ms,
+            } => {
+                if now >= self.next_change && !values.is_empty() {
+                    self.next_change += Duration::from_millis(*interval_ms);
+                    self.step = (self.step + 1) % values.len();
+                    self.current = values[self.step];
+                }
+            }
+            Schedule::Sinusoidal {
+                min,
+                max,
+                period_ms,
+            } => {
+                let elapsed = now.duration_since(self.start).as_secs_f32();
+                let period = (*period_ms).max(1) as f32 / 1000f32;
+                let phase = elapsed / period * 2f32 * std::f32::consts::PI;
+                let mid = (min + max) / 2f32;
+                let amplitude = (max - min) / 2f32;
+                self.current = mid + amplitude * phase.sin();
+            }
+        }
+        self.current
+    }
+}
+
+pub struct Thresholds {
+    schedules: Vec<ThresholdSchedule>,
+    shared: bool,
+}
+
+impl Thresholds {
+    pub fn new(config: &ThresholdConfig, team_count: usize) -> Self {
+        let seed = match config.seed {
+            Some(seed) => seed,
+            None => rand::random::<u64>(),
+        };
+        println!("Threshold schedule seed: {}", seed);
+
+        let now = Instant::now();
+        let schedules = if config.shared {
+            vec![ThresholdSchedule::new(config.schedule.clone(), seed, now)]
+        } else {
+            (0..team_count)
+                .map(|i| {
+                    let schedule = config.teams.get(i).unwrap_or(&config.schedule).clone();
+                    ThresholdSchedule::new(schedule, seed.wrapping_add(i as u64), now)
+                })
+                .collect()
+        };
+        Thresholds {
+            schedules,
+            shared: config.shared,
+        }
+    }
+
+    pub fn update(&mut self, now: Instant) {
+        for schedule in self.schedules.iter_mut() {
+            schedule.update(now);
+        }
+    }
+
+    pub fn for_team(&self, out_index: usize) -> f32 {
+        let index = if self.shared { 0 } else { out_index };
+        match self.schedules.get(index) {
+            Some(schedule) => schedule.current(),
+            None => self.schedules[0].current(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(seed: u64) -> Vec<f32> {
+        let start = Instant::now();
+        let mut schedule = ThresholdSchedule::new(Schedule::default(), seed, start);
+        (0..50)
+            .map(|i| schedule.update(start + Duration::from_millis(i * 700)))
+            .collect()
+    }
+
+    #[test]
+    fn seeded_random_is_reproducible() {
+        let a = run(7);
+        assert_eq!(a, run(7));
+        assert_ne!(a, run(8));
+        assert_eq!(a[0], 0.9);
+        assert!(a.iter().all(|t| *t >= 0.49 && *t <= 0.95));
+    }
+
+    #[test]
+    fn stepped_cycles() {
+        let start = Instant::now();
+        let schedule = Schedule::Stepped {
+            values: vec![0.5, 0.7],
+            interval_ms: 100,
+        };
+        let mut schedule = ThresholdSchedule::new(schedule, 0, start);
+        assert_eq!(schedule.current(), 0.5);
+        assert_eq!(schedule.update(start + Duration::from_millis(100)), 0.7);
+        assert_eq!(schedule.update(start + Duration::from_millis(200)), 0.5);
+    }
+
+    #[test]
+    fn reversed_bounds_are_sorted() {
+        let json = r#"{
+            "mode": "random",
+            "initial": 0.5,
+            "initial_ms": 0,
+            "distribution": "uniform",
+            "range": [0.2, 0.8],
+            "clamp": [0.7, 0.3],
+            "interval_ms": [500, 100]
+        }"#;
+        let schedule: Schedule = serde_json::from_str(json).unwrap();
+        let start = Instant::now();
+        let mut schedule = ThresholdSchedule::new(schedule, 3, start);
+        let t = schedule.update(start);
+        assert!((0.3..=0.7).contains(&t));
+    }
+}
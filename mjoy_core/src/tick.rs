@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TickConfig {
+    #[serde(default = "default_tick_hz")]
+    pub tick_hz: f32,
+    #[serde(default = "default_render_hz")]
+    pub render_hz: f32,
+    // How often measured jitter is printed, 0 turns the report off
+    #[serde(default = "default_report_secs")]
+    pub report_secs: u64,
+}
+
+fn default_tick_hz() -> f32 {
+    250f32
+}
+
+fn default_render_hz() -> f32 {
+    20f32
+}
+
+fn default_report_secs() -> u64 {
+    10
+}
+
+impl Default for TickConfig {
+    fn default() -> Self {
+        TickConfig {
+            tick_hz: default_tick_hz(),
+            render_hz: default_render_hz(),
+            report_secs: default_report_secs(),
+        }
+    }
+}
+
+fn period(hz: f32) -> Duration {
+    Duration::from_nanos((1e9 / hz.max(1f32) as f64) as u64)
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct JitterStats {
+    pub ticks: u64,
+    pub overruns: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl JitterStats {
+    // `late` is how far past its deadline a tick started
+    pub fn record(&mut self, late: Duration, overrun: bool) {
+        self.ticks += 1;
+        self.total += late;
+        self.max = self.max.max(late);
+        if overrun {
+            self.overruns += 1;
+        }
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.ticks {
+            0 => Duration::ZERO,
+            n => self.total / n as u32,
+        }
+    }
+}
+
+pub struct Ticker {
+    period: Duration,
+    render_period: Duration,
+    report_every: Option<Duration>,
+    next_tick: Instant,
+    next_render: Instant,
+    next_report: Instant,
+    stats: JitterStats,
+}
+
+impl Ticker {
+    pub fn new(config: &TickConfig) -> Self {
+        let now = Instant::now();
+        let report_every = match config.report_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+        Ticker {
+            period: period(config.tick_hz),
+            render_period: period(config.render_hz),
+            report_every,
+            next_tick: now,
+            next_render: now,
+            next_report: now + report_every.unwrap_or_default(),
+            stats: JitterStats::default(),
+        }
+    }
+
+    // Sleeps until the next tick is due and returns when it started
+    pub fn wait(&mut self) -> Instant {
+        let now = Instant::now();
+        if now < self.next_tick {
+            std::thread::sleep(self.next_tick - now);
+        }
+        let now = Instant::now();
+        self.advance(now);
+        self.report(now);
+        now
+    }
+
+    fn advance(&mut self, now: Instant) {
+        let late = now.saturating_duration_since(self.next_tick);
+        self.next_tick += self.period;
+        // Drop ticks we are too late for rather than running them back to back
+        let overrun = self.next_tick <= now;
+        if overrun {
+            self.next_tick = now + self.period;
+        }
+        self.stats.record(late, overrun);
+    }
+
+    fn report(&mut self, now: Instant) {
+        let every = match self.report_every {
+            Some(every) => every,
+            None => return,
+        };
+        if now < self.next_report {
+            return;
+        }
+        self.next_report = now + every;
+        println!(
+            "Tick jitter over {} ticks: mean {:?}, max {:?}, {} overruns",
+            self.stats.ticks,
+            self.stats.mean(),
+            self.stats.max,
+            self.stats.overruns
+        );
+        self.stats = JitterStats::default();
+    }
+
+    pub fn render_due(&mut self, now: Instant) -> bool {
+        if now < self.next_render {
+            return false;
+        }
+        self.next_render = now + self.render_period;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn late_ticks_are_dropped_and_counted() {
+        let config = TickConfig {
+            tick_hz: 100f32,
+            render_hz: 10f32,
+            report_secs: 0,
+        };
+        let mut ticker = Ticker::new(&config);
+        let start = ticker.next_tick;
+
+        ticker.advance(start + Duration::from_millis(2));
+        assert_eq!(ticker.next_tick, start + Duration::from_millis(10));
+
+        ticker.advance(start + Duration::from_millis(35));
+        assert_eq!(ticker.next_tick, start + Duration::from_millis(45));
+        assert_eq!(ticker.stats.ticks, 2);
+        assert_eq!(ticker.stats.overruns, 1);
+        assert_eq!(ticker.stats.max, Duration::from_millis(25));
+
+        assert!(ticker.render_due(start));
+        assert!(!ticker.render_due(start + Duration::from_millis(50)));
+        assert!(ticker.render_due(start + Duration::from_millis(100)));
+    }
+}
@@ -0,0 +1,199 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::time::{Duration, Instant};
+
+// Rejected at load when Duration can't hold it: negative, NaN or far too long
+fn rotate_secs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    let secs = match Option::<f32>::deserialize(deserializer)? {
+        Some(secs) => secs,
+        None => return Ok(None),
+    };
+    match Duration::try_from_secs_f32(secs) {
+        Ok(d) if !d.is_zero() => Ok(Some(d)),
+        _ => Err(de::Error::custom(format!(
+            "rotate_secs must be a positive number of seconds, not {}",
+            secs
+        ))),
+    }
+}
+
+fn rotate_secs_out<S: Serializer>(d: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    d.map(|d| d.as_secs_f32()).serialize(serializer)
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum TeamMode {
+    #[default]
+    Average,
+    TurnTaking {
+        #[serde(
+            default,
+            deserialize_with = "rotate_secs",
+            serialize_with = "rotate_secs_out"
+        )]
+        rotate_secs: Option<Duration>,
+        // Control name the active player presses to hand the turn on
+        #[serde(default)]
+        pass_button: Option<String>,
+    },
+}
+
+pub struct Turns {
+    mode: TeamMode,
+    player_count: usize,
+    active: usize,
+    next_rotation: Option<Instant>,
+    pass_held: bool,
+}
+
+impl Turns {
+    pub fn new(mode: TeamMode, player_count: usize) -> Self {
+        let mut turns = Turns {
+            mode,
+            player_count,
+            active: 0,
+            next_rotation: None,
+            pass_held: false,
+        };
+        turns.schedule(Instant::now());
+        turns
+    }
+
+    fn schedule(&mut self, now: Instant) {
+        self.next_rotation = match &self.mode {
+            TeamMode::TurnTaking {
+                rotate_secs: Some(secs),
+                ..
+            } => Some(now + *secs),
+            _ => None,
+        };
+    }
+
+    // On to the next connected player, or nowhere when nobody else is
+    fn advance(&mut self, connected: &[bool], now: Instant) {
+        for step in 1..=self.player_count {
+            let next = (self.active + step) % self.player_count;
+            if connected.get(next).copied().unwrap_or(false) {
+                self.active = next;
+                break;
+            }
+        }
+        self.schedule(now);
+    }
+
+    pub fn pass_button(&self) -> Option<&str> {
+        match &self.mode {
+            TeamMode::TurnTaking {
+                pass_button: Some(b),
+                ..
+            } => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn active_index(&self) -> Option<usize> {
+        match self.mode {
+            TeamMode::Average => None,
+            TeamMode::TurnTaking { .. } => Some(self.active),
+        }
+    }
+
+    pub fn is_active(&self, player_index: usize) -> bool {
+        match self.active_index() {
+            None => true,
+            Some(active) => active == player_index,
+        }
+    }
+
+    // `connected` is by player index. A turn held by someone who isn't
+    // connected is passed on straight away.
+    pub fn update(&mut self, pass_pressed: bool, connected: &[bool], now: Instant) {
+        if let TeamMode::Average = self.mode {
+            return;
+        }
+
+        let passed = pass_pressed && !self.pass_held;
+        self.pass_held = pass_pressed;
+
+        let expired = match self.next_rotation {
+            Some(t) => now >= t,
+            None => false,
+        };
+
+        let absent = !connected.get(self.active).copied().unwrap_or(false);
+
+        if passed || expired || absent {
+            self.advance(connected, now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_on_timer_and_pass() {
+        let mode = TeamMode::TurnTaking {
+            rotate_secs: Some(Duration::from_secs(1)),
+            pass_button: Some("Select".to_string()),
+        };
+        let start = Instant::now();
+        let mut turns = Turns::new(mode, 3);
+        let all = [true; 3];
+        assert!(turns.is_active(0));
+
+        turns.update(false, &all, start + Duration::from_secs(2));
+        assert!(turns.is_active(1));
+
+        turns.update(true, &all, start + Duration::from_secs(2));
+        turns.update(true, &all, start + Duration::from_secs(2));
+        assert!(turns.is_active(2));
+
+        turns.update(false, &all, start + Duration::from_secs(2));
+        turns.update(true, &all, start + Duration::from_secs(2));
+        assert!(turns.is_active(0));
+    }
+
+    #[test]
+    fn average_mode_everyone_plays() {
+        let turns = Turns::new(TeamMode::Average, 3);
+        assert!((0..3).all(|i| turns.is_active(i)));
+        assert_eq!(turns.active_index(), None);
+    }
+
+    #[test]
+    fn rotate_secs_must_be_positive() {
+        let parse = |json| serde_json::from_str::<TeamMode>(json);
+        assert!(parse(r#"{"mode": "turn_taking", "rotate_secs": -1.0}"#).is_err());
+        assert!(parse(r#"{"mode": "turn_taking", "rotate_secs": 0}"#).is_err());
+        assert!(parse(r#"{"mode": "turn_taking", "rotate_secs": 1e30}"#).is_err());
+        assert!(parse(r#"{"mode": "turn_taking", "rotate_secs": 2.5}"#).is_ok());
+        assert!(parse(r#"{"mode": "turn_taking"}"#).is_ok());
+    }
+
+    #[test]
+    fn turns_skip_players_who_are_not_connected() {
+        let mode = TeamMode::TurnTaking {
+            rotate_secs: None,
+            pass_button: Some("Select".to_string()),
+        };
+        let now = Instant::now();
+        let mut turns = Turns::new(mode, 3);
+
+        // Nobody is left to wait on player 0
+        turns.update(false, &[false, true, true], now);
+        assert!(turns.is_active(1));
+
+        // Passing goes round player 0
+        turns.update(true, &[false, true, true], now);
+        turns.update(false, &[false, true, true], now);
+        turns.update(true, &[false, true, true], now);
+        assert!(turns.is_active(1));
+
+        // Alone, the turn stays put
+        turns.update(false, &[false, true, false], now);
+        turns.update(true, &[false, true, false], now);
+        assert!(turns.is_active(1));
+    }
+}
@@ -0,0 +1,519 @@
+use crate::layout::{Layout, OutAxis, OutButton};
+use crate::outjoy::Frame;
+use crate::output::Output;
+use crate::rumble::{Effects, Rumble};
+use nix::libc;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const EV_FF: u16 = 0x15;
+const EV_UINPUT: u16 = 0x0101;
+const SYN_REPORT: u16 = 0;
+const UI_FF_UPLOAD: u16 = 1;
+const UI_FF_ERASE: u16 = 2;
+const FF_RUMBLE: u16 = 0x50;
+const FF_GAIN: u16 = 0x60;
+const FF_EFFECTS_MAX: u32 = 16;
+const BUS_USB: u16 = 0x03;
+const UINPUT_MAX_NAME_SIZE: usize = 80;
+
+#[repr(C)]
+struct UinputSetup {
+    id: libc::input_id,
+    name: [u8; UINPUT_MAX_NAME_SIZE],
+    ff_effects_max: u32,
+}
+
+#[repr(C)]
+struct UinputAbsSetup {
+    code: u16,
+    absinfo: libc::input_absinfo,
+}
+
+// struct ff_effect, with the union left as raw words. Rumble keeps its strong
+// and weak magnitudes in the first two u16s.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FfEffect {
+    type_: u16,
+    id: i16,
+    direction: u16,
+    trigger: [u16; 2],
+    // length and delay, in ms
+    replay: [u16; 2],
+    #[cfg(target_pointer_width = "64")]
+    u: [u64; 4],
+    #[cfg(target_pointer_width = "32")]
+    u: [u32; 7],
+}
+
+#[repr(C)]
+struct UinputFfUpload {
+    request_id: u32,
+    retval: i32,
+    effect: FfEffect,
+    old: FfEffect,
+}
+
+#[repr(C)]
+struct UinputFfErase {
+    request_id: u32,
+    retval: i32,
+    effect_id: u32,
+}
+
+nix::ioctl_none!(ui_dev_create, b'U', 1);
+nix::ioctl_none!(ui_dev_destroy, b'U', 2);
+nix::ioctl_write_ptr!(ui_dev_setup, b'U', 3, UinputSetup);
+nix::ioctl_write_ptr!(ui_abs_setup, b'U', 4, UinputAbsSetup);
+nix::ioctl_write_int!(ui_set_evbit, b'U', 100);
+nix::ioctl_write_int!(ui_set_keybit, b'U', 101);
+nix::ioctl_write_int!(ui_set_absbit, b'U', 103);
+nix::ioctl_write_int!(ui_set_ffbit, b'U', 107);
+nix::ioctl_readwrite!(ui_begin_ff_upload, b'U', 200, UinputFfUpload);
+nix::ioctl_write_ptr!(ui_end_ff_upload, b'U', 201, UinputFfUpload);
+nix::ioctl_readwrite!(ui_begin_ff_erase, b'U', 202, UinputFfErase);
+nix::ioctl_write_ptr!(ui_end_ff_erase, b'U', 203, UinputFfErase);
+
+const ALL_BUTTONS: [OutButton; 14] = [
+    OutButton::LeftNorth,
+    OutButton::LeftSouth,
+    OutButton::LeftEast,
+    OutButton::LeftWest,
+    OutButton::RightNorth,
+    OutButton::RightSouth,
+    OutButton::RightEast,
+    OutButton::RightWest,
+    OutButton::LeftSpecial,
+    OutButton::RightSpecial,
+    OutButton::L1,
+    OutButton::L2,
+    OutButton::R1,
+    OutButton::R2,
+];
+
+const ALL_AXES: [OutAxis; 4] = [OutAxis::X, OutAxis::Y, OutAxis::RX, OutAxis::RY];
+
+// Linux input event codes, as an Xbox 360 pad reports them
+fn button_code(b: OutButton) -> u16 {
+    match b {
+        OutButton::RightSouth => 0x130,   // BTN_SOUTH
+        OutButton::RightEast => 0x131,    // BTN_EAST
+        OutButton::RightNorth => 0x133,   // BTN_NORTH
+        OutButton::RightWest => 0x134,    // BTN_WEST
+        OutButton::L1 => 0x136,           // BTN_TL
+        OutButton::R1 => 0x137,           // BTN_TR
+        OutButton::L2 => 0x138,           // BTN_TL2
+        OutButton::R2 => 0x139,           // BTN_TR2
+        OutButton::LeftSpecial => 0x13a,  // BTN_SELECT
+        OutButton::RightSpecial => 0x13b, // BTN_START
+        OutButton::LeftNorth => 0x220,    // BTN_DPAD_UP
+        OutButton::LeftSouth => 0x221,    // BTN_DPAD_DOWN
+        OutButton::LeftWest => 0x222,     // BTN_DPAD_LEFT
+        OutButton::LeftEast => 0x223,     // BTN_DPAD_RIGHT
+    }
+}
+
+fn axis_code(a: OutAxis) -> u16 {
+    match a {
+        OutAxis::X => 0x00,  // ABS_X
+        OutAxis::Y => 0x01,  // ABS_Y
+        OutAxis::RX => 0x03, // ABS_RX
+        OutAxis::RY => 0x04, // ABS_RY
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AxisRange {
+    pub axis: OutAxis,
+    #[serde(default = "default_min")]
+    pub min: i32,
+    #[serde(default = "default_max")]
+    pub max: i32,
+    #[serde(default)]
+    pub fuzz: i32,
+    #[serde(default)]
+    pub flat: i32,
+    // Flips the axis on top of the evdev direction
+    #[serde(default)]
+    pub invert: bool,
+}
+
+fn default_min() -> i32 {
+    i16::MIN as i32
+}
+
+fn default_max() -> i32 {
+    i16::MAX as i32
+}
+
+impl AxisRange {
+    fn full(axis: OutAxis) -> Self {
+        AxisRange {
+            axis,
+            min: default_min(),
+            max: default_max(),
+            fuzz: 0,
+            flat: 0,
+            invert: false,
+        }
+    }
+
+    // -1..1 onto min..max
+    pub fn scale(&self, value: f32) -> i32 {
+        let value = (value.clamp(-1f32, 1f32) + 1f32) / 2f32;
+        let span = (self.max - self.min) as f32;
+        self.min + (value * span).round() as i32
+    }
+
+    // A frame value as this axis reports it. evdev has Y and RY positive down,
+    // the other way round from frames.
+    pub fn position(&self, value: f32) -> i32 {
+        let value = match self.axis {
+            OutAxis::Y | OutAxis::RY => -value,
+            OutAxis::X | OutAxis::RX => value,
+        };
+        self.scale(if self.invert { -value } else { value })
+    }
+}
+
+// What a team's virtual joystick looks like to games. Impersonating a known pad,
+// e.g. an Xbox 360 pad (045e:028e, version 0110), gets it mapped automatically.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceConfig {
+    // Defaults to BusterN
+    #[serde(default)]
+    pub name: Option<String>,
+    // Hex, like "045e"
+    #[serde(default, with = "crate::hex_id")]
+    pub vendor_id: Option<u16>,
+    #[serde(default, with = "crate::hex_id")]
+    pub product_id: Option<u16>,
+    #[serde(default, with = "crate::hex_id")]
+    pub version: Option<u16>,
+    // Every button and axis a layout can output when unset
+    #[serde(default)]
+    pub buttons: Option<Vec<OutButton>>,
+    #[serde(default)]
+    pub axes: Option<Vec<AxisRange>>,
+}
+
+impl DeviceConfig {
+    pub fn ids(&self) -> [u16; 3] {
+        [
+            self.vendor_id.unwrap_or(0),
+            self.product_id.unwrap_or(0),
+            self.version.unwrap_or(0),
+        ]
+    }
+
+    pub fn name(&self, index: u32) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("Buster{}", index),
+        }
+    }
+
+    pub fn buttons(&self) -> Vec<OutButton> {
+        match &self.buttons {
+            Some(buttons) => buttons.clone(),
+            None => ALL_BUTTONS.to_vec(),
+        }
+    }
+
+    pub fn axes(&self) -> Vec<AxisRange> {
+        match &self.axes {
+            Some(axes) => axes.clone(),
+            None => ALL_AXES.iter().map(|a| AxisRange::full(*a)).collect(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UinputError {
+    #[error("Failed to open /dev/uinput: {0}")]
+    Open(#[from] std::io::Error),
+    #[error("uinput setup failed: {0}")]
+    Ioctl(#[from] nix::Error),
+}
+
+// A uinput device with a fixed set of keys and absolute axes. Events queue up
+// until `sync`. With rumble on, games can upload and play rumble effects on it.
+pub struct Raw {
+    file: File,
+    pending: Vec<libc::input_event>,
+    effects: Effects,
+}
+
+impl Raw {
+    // ids are vendor, product and version
+    pub fn new(
+        name: &str,
+        ids: [u16; 3],
+        keys: &[u16],
+        axes: &[(u16, AxisRange)],
+        rumble: bool,
+    ) -> Result<Self, UinputError> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/uinput")?;
+        let fd = file.as_raw_fd();
+
+        let mut setup = UinputSetup {
+            id: libc::input_id {
+                bustype: BUS_USB,
+                vendor: ids[0],
+                product: ids[1],
+                version: ids[2],
+            },
+            name: [0; UINPUT_MAX_NAME_SIZE],
+            ff_effects_max: if rumble { FF_EFFECTS_MAX } else { 0 },
+        };
+        // Leaves room for the terminating nul
+        let len = name.len().min(UINPUT_MAX_NAME_SIZE - 1);
+        setup.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+        unsafe {
+            ui_set_evbit(fd, EV_KEY as libc::c_ulong)?;
+            for key in keys.iter() {
+                ui_set_keybit(fd, *key as libc::c_ulong)?;
+            }
+            if !axes.is_empty() {
+                ui_set_evbit(fd, EV_ABS as libc::c_ulong)?;
+            }
+            for (code, range) in axes.iter() {
+                ui_set_absbit(fd, *code as libc::c_ulong)?;
+                let abs = UinputAbsSetup {
+                    code: *code,
+                    absinfo: libc::input_absinfo {
+                        value: range.scale(0f32),
+                        minimum: range.min,
+                        maximum: range.max,
+                        fuzz: range.fuzz,
+                        flat: range.flat,
+                        resolution: 0,
+                    },
+                };
+                ui_abs_setup(fd, &abs)?;
+            }
+            if rumble {
+                ui_set_evbit(fd, EV_FF as libc::c_ulong)?;
+                ui_set_ffbit(fd, FF_RUMBLE as libc::c_ulong)?;
+                ui_set_ffbit(fd, FF_GAIN as libc::c_ulong)?;
+            }
+            ui_dev_setup(fd, &setup)?;
+            ui_dev_create(fd)?;
+        }
+
+        Ok(Raw {
+            file,
+            pending: Vec::new(),
+            effects: Effects::default(),
+        })
+    }
+
+    fn emit(&mut self, kind: u16, code: u16, value: i32) {
+        self.pending.push(libc::input_event {
+            time: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            type_: kind,
+            code,
+            value,
+        });
+    }
+
+    pub fn key(&mut self, code: u16, pressed: bool) {
+        self.emit(EV_KEY, code, pressed as i32);
+    }
+
+    pub fn axis(&mut self, code: u16, value: i32) {
+        self.emit(EV_ABS, code, value);
+    }
+
+    pub fn sync(&mut self) {
+        self.emit(EV_SYN, SYN_REPORT, 0);
+        let events = std::mem::take(&mut self.pending);
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                events.as_ptr() as *const u8,
+                events.len() * std::mem::size_of::<libc::input_event>(),
+            )
+        };
+        self.file
+            .write_all(bytes)
+            .expect("Failed to write to a uinput device");
+    }
+
+    fn read_event(&mut self) -> Option<libc::input_event> {
+        let mut event = std::mem::MaybeUninit::<libc::input_event>::zeroed();
+        let bytes = unsafe {
+            std::slice::from_raw_parts_mut(
+                event.as_mut_ptr() as *mut u8,
+                std::mem::size_of::<libc::input_event>(),
+            )
+        };
+        match self.file.read(bytes) {
+            Ok(n) if n == bytes.len() => Some(unsafe { event.assume_init() }),
+            _ => None,
+        }
+    }
+
+    fn upload(&mut self, request_id: u32) -> Result<(), nix::Error> {
+        let fd = self.file.as_raw_fd();
+        let mut upload: UinputFfUpload = unsafe { std::mem::zeroed() };
+        upload.request_id = request_id;
+        unsafe { ui_begin_ff_upload(fd, &mut upload)? };
+        let effect = upload.effect;
+        if effect.type_ == FF_RUMBLE {
+            let magnitudes = unsafe { *(effect.u.as_ptr() as *const [u16; 2]) };
+            self.effects
+                .upload(effect.id, magnitudes[0], magnitudes[1], effect.replay[0]);
+            upload.retval = 0;
+        } else {
+            upload.retval = -libc::EINVAL;
+        }
+        unsafe { ui_end_ff_upload(fd, &upload)? };
+        Ok(())
+    }
+
+    fn erase(&mut self, request_id: u32) -> Result<(), nix::Error> {
+        let fd = self.file.as_raw_fd();
+        let mut erase = UinputFfErase {
+            request_id,
+            retval: 0,
+            effect_id: 0,
+        };
+        unsafe { ui_begin_ff_erase(fd, &mut erase)? };
+        self.effects.erase(erase.effect_id as i16);
+        unsafe { ui_end_ff_erase(fd, &erase)? };
+        Ok(())
+    }
+
+    // Answers effect uploads and erases, and returns the rumbles played since
+    // the last call
+    pub fn rumble(&mut self) -> Vec<Rumble> {
+        let mut played = Vec::new();
+        while let Some(event) = self.read_event() {
+            let handled = match (event.type_, event.code) {
+                (EV_UINPUT, UI_FF_UPLOAD) => self.upload(event.value as u32),
+                (EV_UINPUT, UI_FF_ERASE) => self.erase(event.value as u32),
+                (EV_FF, FF_GAIN) => {
+                    self.effects.set_gain(event.value as u16);
+                    Ok(())
+                }
+                (EV_FF, id) => {
+                    played.extend(self.effects.play(id as i16, event.value));
+                    Ok(())
+                }
+                _ => Ok(()),
+            };
+            if let Err(e) = handled {
+                eprintln!("Failed to answer a force feedback request: {}", e);
+            }
+        }
+        played
+    }
+}
+
+impl Drop for Raw {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = ui_dev_destroy(self.file.as_raw_fd());
+        }
+    }
+}
+
+// A team's virtual joystick
+pub struct Device {
+    raw: Raw,
+    buttons: Vec<OutButton>,
+    axes: Vec<AxisRange>,
+}
+
+impl Device {
+    pub fn new(config: &DeviceConfig, name: &str) -> Result<Self, UinputError> {
+        let buttons = config.buttons();
+        let axes = config.axes();
+        let keys: Vec<u16> = buttons.iter().map(|b| button_code(*b)).collect();
+        let abs: Vec<(u16, AxisRange)> = axes.iter().map(|r| (axis_code(r.axis), *r)).collect();
+        Ok(Device {
+            raw: Raw::new(name, config.ids(), &keys, &abs, true)?,
+            buttons,
+            axes,
+        })
+    }
+}
+
+impl Output for Device {
+    fn write(&mut self, layout: &Layout, frame: &Frame) {
+        for (inaxis, value) in layout.axes.iter().zip(frame.axes.iter()) {
+            let range = match self.axes.iter().find(|r| r.axis == inaxis.output) {
+                Some(range) => *range,
+                None => continue,
+            };
+            self.raw.axis(axis_code(range.axis), range.position(*value));
+        }
+        for (inbutton, pressed) in layout.buttons.iter().zip(frame.buttons.iter()) {
+            if self.buttons.contains(&inbutton.output) {
+                self.raw.key(button_code(inbutton.output), *pressed);
+            }
+        }
+    }
+
+    fn synchronise(&mut self) {
+        self.raw.sync();
+    }
+
+    fn rumble(&mut self) -> Vec<Rumble> {
+        self.raw.rumble()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xbox_device_config() {
+        let json = r#"{
+            "name": "Microsoft X-Box 360 pad",
+            "vendor_id": "045e",
+            "product_id": "028e",
+            "version": "0110",
+            "buttons": ["RightSouth", "RightEast", "RightSpecial"],
+            "axes": [{"axis": "X", "min": -32768, "max": 32767, "fuzz": 16, "flat": 128}]
+        }"#;
+        let config: DeviceConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.name(0), "Microsoft X-Box 360 pad");
+        assert_eq!(config.ids(), [0x045e, 0x028e, 0x0110]);
+        assert_eq!(config.buttons().len(), 3);
+
+        let x = config.axes()[0];
+        assert_eq!(x.scale(-1f32), -32768);
+        assert_eq!(x.scale(1f32), 32767);
+        assert_eq!(x.scale(0f32), 0);
+
+        let default = DeviceConfig::default();
+        assert_eq!(default.name(2), "Buster2");
+        assert_eq!(default.buttons().len(), ALL_BUTTONS.len());
+        assert_eq!(default.axes()[1].scale(0.5f32), 16383);
+
+        // Up on Y is negative, unless inverted
+        assert_eq!(x.position(0.5f32), 16383);
+        let y = default.axes()[1];
+        assert_eq!(y.axis, OutAxis::Y);
+        assert_eq!(y.position(1f32), -32768);
+        let y = AxisRange { invert: true, ..y };
+        assert_eq!(y.position(1f32), 32767);
+    }
+}
@@ -6,6 +6,15 @@ pub struct FeedbackInfo<'a> {
 pub struct Player {
     pub player_name: String,
     pub feedback: Presses,
+    pub active: bool,
+    pub assigned: Option<Vec<String>>,
+    pub connection: Connection,
+}
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Connection {
+    Connected,
+    Reconnecting,
+    Disconnected,
 }
 #[derive(Clone, PartialEq, Eq)]
 pub enum PressState {
@@ -25,4 +34,7 @@ pub struct Team<'a> {
     pub team_name: &'a str,
     pub players: Vec<Player>,
     pub feedback: Presses,
+    pub threshold: Option<f32>,
+    pub modifiers: Vec<String>,
+    pub modifiers_enabled: bool,
 }
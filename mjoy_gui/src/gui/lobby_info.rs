@@ -0,0 +1,16 @@
+#[derive(Clone)]
+pub struct LobbyInfo {
+    pub teams: Vec<LobbyTeam>,
+}
+#[derive(Clone)]
+pub struct LobbyPlayer {
+    pub player_name: String,
+    pub confirmed: bool,
+}
+#[derive(Clone)]
+pub struct LobbyTeam {
+    pub label: String,
+    pub capacity: u32,
+    // Everyone currently pointing at the team, confirmed or not
+    pub players: Vec<LobbyPlayer>,
+}
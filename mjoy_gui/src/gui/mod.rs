@@ -4,7 +4,9 @@ use kiss3d::window::Window;
 use crate::manipulate_emulator;
 
 use self::feedback_info::FeedbackInfo;
+use self::lobby_info::LobbyInfo;
 pub mod feedback_info;
+pub mod lobby_info;
 mod team_color;
 
 struct Hz(f32);
@@ -62,6 +64,7 @@ impl<'a> RatioXY<'a> {
 enum SubtextInfo {
     Myself,
     Button(i32),
+    Caption(i32),
 }
 
 struct DrawPlayerInfo {
@@ -80,25 +83,53 @@ struct DrawTextInfo<'a> {
     text: &'a str,
     sub: SubtextInfo,
     color_override: bool,
+    dim: bool,
 }
 
 const TEXTURE_SIZE: f32 = 220f32;
 
 impl Ui {
     pub fn new(teams: &[String], width_height: WidthHeight) -> Ui {
+        let mut ui = Ui::without_teams(width_height);
+        ui.set_teams(teams);
+        ui
+    }
+
+    // Opens the window before teams are known, for the lobby
+    pub fn without_teams(width_height: WidthHeight) -> Ui {
         let mut window =
             Window::new_with_size("Cool project", width_height.width, width_height.height);
         window.set_background_color(0.1, 0.1, 0.1);
         window.set_light(Light::StickToCamera);
 
-        let texture_size = RatioXY::new(TEXTURE_SIZE, TEXTURE_SIZE, &width_height);
-        let texture_position = RatioXY::new(845f32, 260f32, &width_height);
-        let texture_position_bonus = RatioXY::new(0f32, 150f32, &width_height);
+        Ui {
+            window,
+            teams: Vec::new(),
+            logos: Vec::new(),
+            logos_locations: Vec::new(),
+            colors: team_color::ColoredTeams(Vec::new()),
+            font: kiss3d::text::Font::new(std::path::Path::new("./resources/impact.ttf")).unwrap(),
+            width_height,
+            did_gui_on: false,
+            did_gui_off: false,
+        }
+    }
+
+    pub fn set_teams(&mut self, teams: &[String]) {
+        let width_height = &self.width_height;
+        let texture_size = RatioXY::new(TEXTURE_SIZE, TEXTURE_SIZE, width_height);
+        let texture_position = RatioXY::new(845f32, 260f32, width_height);
+        let texture_position_bonus = RatioXY::new(0f32, 150f32, width_height);
 
+        for logo in self.logos.iter_mut() {
+            self.window.remove_planar_node(logo);
+        }
         let mut logos: Vec<_> = Vec::new();
         let mut trans: Vec<_> = Vec::new();
         for (i, team) in teams.iter().enumerate() {
-            let mut r = window.add_rectangle(texture_size.x(), texture_size.y());
+            let mut r = self
+                .window
+                .add_rectangle(texture_size.x(), texture_size.y());
             let translate = &kiss3d::nalgebra::Translation2::new(
                 texture_position.x() * if i % 2 == 0 { -1 } else { 1 } as f32
                     + texture_position_bonus.x(),
@@ -118,18 +149,62 @@ impl Ui {
             hc.color_teams(&teams.as_slice())
         };
 
-        let ui = Ui {
-            window,
-            teams: teams.iter().map(|t| t.to_string()).collect(),
-            logos,
-            logos_locations: trans,
-            colors,
-            font: kiss3d::text::Font::new(std::path::Path::new("./resources/impact.ttf")).unwrap(),
-            width_height,
-            did_gui_on: false,
-            did_gui_off: false,
-        };
-        ui
+        self.teams = teams.iter().map(|t| t.to_string()).collect();
+        self.logos = logos;
+        self.logos_locations = trans;
+        self.colors = colors;
+    }
+
+    // One column per team with the players pointing at it. Unconfirmed players
+    // are dimmed.
+    pub fn render_lobby(&mut self, lobby: &LobbyInfo) {
+        let width = self.width_height.width as f32;
+        let height = self.width_height.height as f32;
+        let columns = lobby.teams.len().max(1) as f32;
+        let white: kiss3d::nalgebra::Point3<f32> = kiss3d::nalgebra::Point3::new(1f32, 1f32, 1f32);
+        let title_size = 85f32 * width / XRATIO_DENOM;
+        let player_size = 70f32 * width / XRATIO_DENOM;
+
+        self.window.draw_text(
+            "Pick a team: left/right to move, A to confirm, B to back out",
+            &kiss3d::nalgebra::Point2::new(
+                100f32 * width / XRATIO_DENOM,
+                60f32 * height / YRATIO_DENOM,
+            ),
+            player_size,
+            &self.font,
+            &white,
+        );
+        for (i, team) in lobby.teams.iter().enumerate() {
+            // Text is positioned in twice the window's size
+            let x = 2f32 * width * (i as f32 + 0.15f32) / columns;
+            let confirmed = team.players.iter().filter(|p| p.confirmed).count();
+            self.window.draw_text(
+                &format!("{} ({}/{})", team.label, confirmed, team.capacity),
+                &kiss3d::nalgebra::Point2::new(x, 300f32 * height / YRATIO_DENOM),
+                title_size,
+                &self.font,
+                &white,
+            );
+            for (j, player) in team.players.iter().enumerate() {
+                let color = if player.confirmed {
+                    white
+                } else {
+                    white * 0.35f32
+                };
+                self.window.draw_text(
+                    &player.player_name,
+                    &kiss3d::nalgebra::Point2::new(
+                        x + 50f32 * width / XRATIO_DENOM,
+                        (420f32 + 113f32 * j as f32) * height / YRATIO_DENOM,
+                    ),
+                    player_size,
+                    &self.font,
+                    &color,
+                );
+            }
+        }
+        self.window.render();
     }
 
     pub fn render(&mut self, feedback: &FeedbackInfo, show_logos: bool) {
@@ -165,9 +240,40 @@ impl Ui {
                 text: &team.team_name,
                 sub: SubtextInfo::Myself,
                 color_override: !show_logos,
+                dim: false,
             };
+            let threshold_text = team
+                .threshold
+                .map(|t| format!("{:.0}%", t * 100f32))
+                .unwrap_or_default();
+            let modifiers_text = team.modifiers.join(" ");
+            let player_texts: Vec<String> = team
+                .players
+                .iter()
+                .map(|player| match player.connection {
+                    feedback_info::Connection::Connected => player.player_name.clone(),
+                    feedback_info::Connection::Reconnecting => {
+                        format!("{} (waiting)", player.player_name)
+                    }
+                    feedback_info::Connection::Disconnected => {
+                        format!("{} (gone)", player.player_name)
+                    }
+                })
+                .collect();
             if show_logos {
                 self.draw_text(&draw_text_info);
+                if team.threshold.is_some() {
+                    draw_text_info.text = &threshold_text;
+                    draw_text_info.sub = SubtextInfo::Caption(0);
+                    self.draw_text(&draw_text_info);
+                }
+                if !team.modifiers.is_empty() {
+                    draw_text_info.text = &modifiers_text;
+                    draw_text_info.sub = SubtextInfo::Caption(3);
+                    draw_text_info.dim = !team.modifiers_enabled;
+                    self.draw_text(&draw_text_info);
+                    draw_text_info.dim = false;
+                }
                 for (i, fb) in team.feedback.0.iter().enumerate() {
                     if fb.state == feedback_info::PressState::Unpressed {
                         continue;
@@ -182,16 +288,24 @@ impl Ui {
             for (i, player) in team.players.iter().enumerate() {
                 draw_text_info.team_or_player =
                     TeamOrPlayer::Player(DrawPlayerInfo { player_index: i });
-                draw_text_info.text = &player.player_name;
+                draw_text_info.text = &player_texts[i];
                 draw_text_info.sub = SubtextInfo::Myself;
+                draw_text_info.dim =
+                    !player.active || player.connection != feedback_info::Connection::Connected;
                 self.draw_text(&draw_text_info);
                 for (i, fb) in player.feedback.0.iter().enumerate() {
-                    if fb.state == feedback_info::PressState::Unpressed {
+                    let assigned = match &player.assigned {
+                        Some(assigned) => assigned.contains(&fb.button),
+                        None => false,
+                    };
+                    if fb.state == feedback_info::PressState::Unpressed && !assigned {
                         continue;
                     }
 
                     draw_text_info.text = &fb.button;
                     draw_text_info.sub = SubtextInfo::Button(i as i32);
+                    draw_text_info.dim =
+                        !player.active || fb.state == feedback_info::PressState::Unpressed;
                     self.draw_text(&draw_text_info);
                 }
             }
@@ -199,6 +313,34 @@ impl Ui {
         self.window.render();
     }
 
+    // Function keys pressed since the last render, F1 is 1
+    pub fn function_keys(&mut self) -> Vec<u32> {
+        use kiss3d::event::{Action, Key, WindowEvent};
+
+        let mut keys = Vec::new();
+        for event in self.window.events().iter() {
+            if let WindowEvent::Key(key, Action::Press, _) = event.value {
+                let n = match key {
+                    Key::F1 => 1,
+                    Key::F2 => 2,
+                    Key::F3 => 3,
+                    Key::F4 => 4,
+                    Key::F5 => 5,
+                    Key::F6 => 6,
+                    Key::F7 => 7,
+                    Key::F8 => 8,
+                    Key::F9 => 9,
+                    Key::F10 => 10,
+                    Key::F11 => 11,
+                    Key::F12 => 12,
+                    _ => continue,
+                };
+                keys.push(n);
+            }
+        }
+        keys
+    }
+
     fn draw_text(&mut self, info: &DrawTextInfo) {
         use TeamOrPlayer::*;
 
@@ -217,6 +359,7 @@ impl Ui {
             + match info.sub {
                 SubtextInfo::Myself => 0f32,
                 SubtextInfo::Button(i) => i as f32 + 0.7f32,
+                SubtextInfo::Caption(i) => i as f32,
             } * (35f32 * self.width_height.width as f32)
                 / XRATIO_DENOM;
 
@@ -235,6 +378,7 @@ impl Ui {
             + match info.sub {
                 SubtextInfo::Myself => 0,
                 SubtextInfo::Button(_) => 1,
+                SubtextInfo::Caption(_) => -1,
             } as f32
                 * (60f32 * self.width_height.height as f32)
                 / YRATIO_DENOM;
@@ -246,6 +390,7 @@ impl Ui {
             * match info.sub {
                 SubtextInfo::Myself => 1f32,
                 SubtextInfo::Button(_) => 0.9f32,
+                SubtextInfo::Caption(_) => 0.6f32,
             }
             * self.width_height.width as f32)
             / XRATIO_DENOM;
@@ -254,7 +399,7 @@ impl Ui {
         let color = color
             * match info.sub {
                 SubtextInfo::Myself => 1f32,
-                SubtextInfo::Button(_) => 0.9f32,
+                SubtextInfo::Button(_) | SubtextInfo::Caption(_) => 0.9f32,
             };
         let color = color
             * match info.team_or_player {
@@ -267,6 +412,10 @@ impl Ui {
             color = kiss3d::nalgebra::Vector3::new(1f32, 1f32, 1f32).into();
         }
 
+        if info.dim {
+            color = color * 0.35f32;
+        }
+
         self.window.draw_text(
             info.text,
             &kiss3d::nalgebra::Point2::new(xpos, ypos),
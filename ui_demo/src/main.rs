@@ -76,12 +76,18 @@ fn main() {
                             gui::feedback_info::Player {
                                 player_name: name,
                                 feedback: gui::feedback_info::Presses(myfb),
+                                active: true,
+                                assigned: None,
+                                connection: gui::feedback_info::Connection::Connected,
                             }
                         })
                         .collect();
                     players
                 },
                 feedback: gui::feedback_info::Presses(fb.clone()),
+                threshold: Some(0.75),
+                modifiers: vec![],
+                modifiers_enabled: true,
             })
             .collect(),
     };
@@ -12,6 +12,10 @@ use std::time::SystemTime;
 mod platform;
 pub mod utils;
 
+/// Minimal libudev wrapper, shared with users that need to do their own device discovery.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
+pub use platform::udev;
+
 /// True, if Y axis of sticks commonly points downwards.
 pub const IS_Y_AXIS_REVERSED: bool = platform::IS_Y_AXIS_REVERSED;
 
@@ -8,7 +8,7 @@
 mod ff;
 mod gamepad;
 mod ioctl;
-mod udev;
+pub mod udev;
 
 pub use self::ff::Device as FfDevice;
 pub use self::gamepad::{native_ev_codes, EvCode, Gamepad, Gilrs};
@@ -126,3 +126,6 @@ pub use crate::gamepad::{
     PowerInfo,
 };
 pub use crate::mapping::{MappingData as Mapping, MappingError};
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
+pub use gilrs_core::udev;